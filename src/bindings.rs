@@ -0,0 +1,699 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use glium::glutin::VirtualKeyCode;
+
+use input::{Button, InputFrame};
+use scene::MenuInput;
+
+/// Something the player can do with a key
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action
+{
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCW,
+    RotateCCW,
+    Rotate180,
+    Hold,
+    Pause,
+    Restart,
+}
+
+pub const ACTIONS: [Action; 10] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateCW,
+    Action::RotateCCW,
+    Action::Rotate180,
+    Action::Hold,
+    Action::Pause,
+    Action::Restart,
+];
+
+impl Action
+{
+    /// The name used in the controls file
+    pub fn name(&self) -> &'static str
+    {
+        match *self
+        {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateCW => "rotate_cw",
+            Action::RotateCCW => "rotate_ccw",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action>
+    {
+        ACTIONS.iter().find(|x| x.name() == name).map(|x| *x)
+    }
+
+    /// The engine button this action holds down. Pause and restart are
+    /// handled outside of the game.
+    pub fn button(&self) -> Option<Button>
+    {
+        match *self
+        {
+            Action::MoveLeft => Some(Button::Left),
+            Action::MoveRight => Some(Button::Right),
+            Action::SoftDrop => Some(Button::SoftDrop),
+            Action::HardDrop => Some(Button::HardDrop),
+            Action::RotateCW => Some(Button::RotateRight),
+            Action::RotateCCW => Some(Button::RotateLeft),
+            Action::Rotate180 => Some(Button::Rotate180),
+            Action::Hold => Some(Button::Hold),
+            Action::Pause | Action::Restart => None,
+        }
+    }
+}
+
+/// How far a stick has to move before it counts, from 0 to 1
+pub const DEFAULT_DEADZONE: f32 = 0.5;
+
+/// One end of a gamepad stick or D-pad axis, written `Axis0-` or `Axis0+` in
+/// the controls file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PadAxis
+{
+    pub axis: u8,
+    /// Right or down rather than left or up
+    pub positive: bool,
+}
+
+impl PadAxis
+{
+    pub fn new(axis: u8, positive: bool) -> PadAxis
+    {
+        PadAxis
+        {
+            axis: axis,
+            positive: positive,
+        }
+    }
+
+    pub fn name(&self) -> String
+    {
+        format!("Axis{}{}", self.axis, if self.positive { "+" } else { "-" })
+    }
+}
+
+/// Which keys and gamepad buttons trigger each action. A key or button
+/// belongs to at most one action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings
+{
+    /// Keys for each action, indexed by `Action`
+    keys: Vec<Vec<VirtualKeyCode>>,
+    /// Gamepad buttons for each action, indexed by `Action`
+    pad_buttons: Vec<Vec<u8>>,
+    /// Gamepad axis ends for each action, indexed by `Action`
+    pad_axes: Vec<Vec<PadAxis>>,
+
+    pub deadzone: f32,
+}
+
+impl Default for Bindings
+{
+    fn default() -> Bindings
+    {
+        let mut bindings = Bindings::empty();
+
+        bindings.set(Action::MoveLeft, &[VirtualKeyCode::Left]);
+        bindings.set(Action::MoveRight, &[VirtualKeyCode::Right]);
+        bindings.set(Action::SoftDrop, &[VirtualKeyCode::Down]);
+        bindings.set(Action::HardDrop, &[VirtualKeyCode::Space]);
+        bindings.set(Action::RotateCW, &[VirtualKeyCode::Up, VirtualKeyCode::Z]);
+        bindings.set(Action::RotateCCW, &[VirtualKeyCode::X]);
+        bindings.set(Action::Rotate180, &[VirtualKeyCode::A]);
+        bindings.set(Action::Hold, &[VirtualKeyCode::C, VirtualKeyCode::LShift]);
+        bindings.set(Action::Pause, &[VirtualKeyCode::Escape, VirtualKeyCode::P]);
+        bindings.set(Action::Restart, &[VirtualKeyCode::R]);
+
+        // Button numbers of an Xbox style pad on Linux
+        bindings.set_pad(Action::RotateCCW, &[0]);
+        bindings.set_pad(Action::RotateCW, &[1]);
+        bindings.set_pad(Action::Rotate180, &[2]);
+        bindings.set_pad(Action::Hold, &[3, 4, 5]);
+        bindings.set_pad(Action::Restart, &[6]);
+        bindings.set_pad(Action::Pause, &[7]);
+
+        // The left stick is axes 0 and 1 and the D-pad 6 and 7
+        bindings.set_pad_axes(Action::MoveLeft, &[PadAxis::new(0, false), PadAxis::new(6, false)]);
+        bindings.set_pad_axes(Action::MoveRight, &[PadAxis::new(0, true), PadAxis::new(6, true)]);
+        bindings.set_pad_axes(Action::SoftDrop, &[PadAxis::new(1, true), PadAxis::new(7, true)]);
+        bindings.set_pad_axes(Action::HardDrop, &[PadAxis::new(7, false)]);
+
+        bindings
+    }
+}
+
+/// Writes the bindings in the format `parse` reads
+impl fmt::Display for Bindings
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        try!(writeln!(f, "# Rustris controls: action = Key, Pad0, Axis0-"));
+
+        for action in ACTIONS.iter()
+        {
+            try!(writeln!(f, "{} = {}", action.name(), self.describe(*action)));
+        }
+
+        writeln!(f, "deadzone = {}", self.deadzone)
+    }
+}
+
+impl Bindings
+{
+    /// Bindings with nothing bound
+    fn empty() -> Bindings
+    {
+        Bindings
+        {
+            keys: ACTIONS.iter().map(|_| vec![]).collect(),
+            pad_buttons: ACTIONS.iter().map(|_| vec![]).collect(),
+            pad_axes: ACTIONS.iter().map(|_| vec![]).collect(),
+
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> &[VirtualKeyCode]
+    {
+        &self.keys[action as usize]
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action>
+    {
+        ACTIONS.iter().find(|x| self.keys(**x).contains(&key)).map(|x| *x)
+    }
+
+    /// Replaces the keys of an action, taking them off any other action
+    pub fn set(&mut self, action: Action, keys: &[VirtualKeyCode])
+    {
+        self.keys[action as usize].clear();
+
+        for key in keys.iter()
+        {
+            self.bind(action, *key);
+        }
+    }
+
+    /// Adds a key to an action, taking it off any other action
+    pub fn bind(&mut self, action: Action, key: VirtualKeyCode)
+    {
+        for keys in self.keys.iter_mut()
+        {
+            keys.retain(|x| *x != key);
+        }
+
+        self.keys[action as usize].push(key);
+    }
+
+    pub fn pad_buttons(&self, action: Action) -> &[u8]
+    {
+        &self.pad_buttons[action as usize]
+    }
+
+    pub fn pad_action(&self, button: u8) -> Option<Action>
+    {
+        ACTIONS.iter().find(|x| self.pad_buttons(**x).contains(&button)).map(|x| *x)
+    }
+
+    /// Replaces the gamepad buttons of an action, taking them off any other
+    /// action
+    pub fn set_pad(&mut self, action: Action, buttons: &[u8])
+    {
+        self.pad_buttons[action as usize].clear();
+
+        for button in buttons.iter()
+        {
+            for pad_buttons in self.pad_buttons.iter_mut()
+            {
+                pad_buttons.retain(|x| x != button);
+            }
+
+            self.pad_buttons[action as usize].push(*button);
+        }
+    }
+
+    pub fn pad_axes(&self, action: Action) -> &[PadAxis]
+    {
+        &self.pad_axes[action as usize]
+    }
+
+    pub fn axis_action(&self, axis: PadAxis) -> Option<Action>
+    {
+        ACTIONS.iter().find(|x| self.pad_axes(**x).contains(&axis)).map(|x| *x)
+    }
+
+    /// Replaces the gamepad axis ends of an action, taking them off any other
+    /// action
+    pub fn set_pad_axes(&mut self, action: Action, axes: &[PadAxis])
+    {
+        self.pad_axes[action as usize].clear();
+
+        for axis in axes.iter()
+        {
+            for pad_axes in self.pad_axes.iter_mut()
+            {
+                pad_axes.retain(|x| x != axis);
+            }
+
+            self.pad_axes[action as usize].push(*axis);
+        }
+    }
+
+    /// The buttons held by a set of keys
+    pub fn frame(&self, keys_down: &[VirtualKeyCode]) -> InputFrame
+    {
+        let mut frame = InputFrame::empty();
+
+        for key in keys_down.iter()
+        {
+            match self.action(*key).and_then(|x| x.button())
+            {
+                Some(button) => frame.set(button, true),
+                None => ()
+            }
+        }
+
+        frame
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Bindings>
+    {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+
+        Bindings::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()>
+    {
+        let mut file = try!(File::create(path));
+        file.write_all(self.to_string().as_bytes())
+    }
+
+    /// Reads `action = Key, Key` lines, where gamepad buttons are written
+    /// `Pad0`, `Pad1` and so on and axes `Axis0-`, `Axis0+`, plus a
+    /// `deadzone = 0.5` line. Actions that aren't mentioned keep their
+    /// default bindings.
+    ///
+    /// Files from before gamepad support have no deadzone line. Their lines
+    /// only replace the gamepad bindings of an action if they name some.
+    pub fn parse(text: &str) -> Result<Bindings, String>
+    {
+        let mut bindings = Bindings::default();
+
+        let has_pad = text.lines()
+            .any(|x| x.split(|c| c == '#' || c == '=').next().unwrap().trim() == "deadzone");
+
+        for (i, line) in text.lines().enumerate()
+        {
+            let line = line.split('#').next().unwrap().trim();
+
+            if line.is_empty()
+            {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+
+            let value = match parts.next()
+            {
+                Some(x) => x,
+                None => return Err(format!("line {}: expected `action = keys`", i + 1))
+            };
+
+            if name == "deadzone"
+            {
+                bindings.deadzone = match value.trim().parse::<f32>()
+                {
+                    Ok(x) if x >= 0.0 && x < 1.0 => x,
+                    _ => return Err(format!("line {}: deadzone must be from 0 to 1", i + 1))
+                };
+
+                continue;
+            }
+
+            let action = match Action::from_name(name)
+            {
+                Some(x) => x,
+                None => return Err(format!("line {}: unknown action `{}`", i + 1, name))
+            };
+
+            let mut keys = vec![];
+            let mut pad_buttons = vec![];
+            let mut pad_axes = vec![];
+
+            for key_name in value.split(',').map(|x| x.trim()).filter(|x| !x.is_empty())
+            {
+                match (key_from_name(key_name), pad_button_from_name(key_name),
+                    pad_axis_from_name(key_name))
+                {
+                    (Some(x), _, _) => keys.push(x),
+                    (None, Some(x), _) => pad_buttons.push(x),
+                    (None, None, Some(x)) => pad_axes.push(x),
+                    (None, None, None) =>
+                        return Err(format!("line {}: unknown key `{}`", i + 1, key_name))
+                }
+            }
+
+            bindings.set(action, &keys);
+
+            if has_pad || !pad_buttons.is_empty() || !pad_axes.is_empty()
+            {
+                bindings.set_pad(action, &pad_buttons);
+                bindings.set_pad_axes(action, &pad_axes);
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    /// The names of everything bound to an action, separated by commas
+    pub fn describe(&self, action: Action) -> String
+    {
+        let keys = self.keys(action).iter().map(|x| key_name(*x).to_string());
+        let pad_buttons = self.pad_buttons(action).iter().map(|x| format!("Pad{}", x));
+        let pad_axes = self.pad_axes(action).iter().map(|x| x.name());
+        let names: Vec<String> = keys.chain(pad_buttons).chain(pad_axes).collect();
+
+        names.join(", ")
+    }
+}
+
+/// Keys that can be bound, with their names in the controls file
+const KEYS: [(&'static str, VirtualKeyCode); 90] = [
+    ("Key1", VirtualKeyCode::Key1), ("Key2", VirtualKeyCode::Key2),
+    ("Key3", VirtualKeyCode::Key3), ("Key4", VirtualKeyCode::Key4),
+    ("Key5", VirtualKeyCode::Key5), ("Key6", VirtualKeyCode::Key6),
+    ("Key7", VirtualKeyCode::Key7), ("Key8", VirtualKeyCode::Key8),
+    ("Key9", VirtualKeyCode::Key9), ("Key0", VirtualKeyCode::Key0),
+    ("A", VirtualKeyCode::A), ("B", VirtualKeyCode::B), ("C", VirtualKeyCode::C),
+    ("D", VirtualKeyCode::D), ("E", VirtualKeyCode::E), ("F", VirtualKeyCode::F),
+    ("G", VirtualKeyCode::G), ("H", VirtualKeyCode::H), ("I", VirtualKeyCode::I),
+    ("J", VirtualKeyCode::J), ("K", VirtualKeyCode::K), ("L", VirtualKeyCode::L),
+    ("M", VirtualKeyCode::M), ("N", VirtualKeyCode::N), ("O", VirtualKeyCode::O),
+    ("P", VirtualKeyCode::P), ("Q", VirtualKeyCode::Q), ("R", VirtualKeyCode::R),
+    ("S", VirtualKeyCode::S), ("T", VirtualKeyCode::T), ("U", VirtualKeyCode::U),
+    ("V", VirtualKeyCode::V), ("W", VirtualKeyCode::W), ("X", VirtualKeyCode::X),
+    ("Y", VirtualKeyCode::Y), ("Z", VirtualKeyCode::Z),
+    ("Escape", VirtualKeyCode::Escape),
+    ("F1", VirtualKeyCode::F1), ("F2", VirtualKeyCode::F2), ("F3", VirtualKeyCode::F3),
+    ("F4", VirtualKeyCode::F4), ("F5", VirtualKeyCode::F5), ("F6", VirtualKeyCode::F6),
+    ("F7", VirtualKeyCode::F7), ("F8", VirtualKeyCode::F8), ("F9", VirtualKeyCode::F9),
+    ("F10", VirtualKeyCode::F10), ("F11", VirtualKeyCode::F11), ("F12", VirtualKeyCode::F12),
+    ("Insert", VirtualKeyCode::Insert), ("Home", VirtualKeyCode::Home),
+    ("Delete", VirtualKeyCode::Delete), ("End", VirtualKeyCode::End),
+    ("PageDown", VirtualKeyCode::PageDown), ("PageUp", VirtualKeyCode::PageUp),
+    ("Left", VirtualKeyCode::Left), ("Up", VirtualKeyCode::Up),
+    ("Right", VirtualKeyCode::Right), ("Down", VirtualKeyCode::Down),
+    ("Back", VirtualKeyCode::Back), ("Return", VirtualKeyCode::Return),
+    ("Space", VirtualKeyCode::Space),
+    ("Numpad0", VirtualKeyCode::Numpad0), ("Numpad1", VirtualKeyCode::Numpad1),
+    ("Numpad2", VirtualKeyCode::Numpad2), ("Numpad3", VirtualKeyCode::Numpad3),
+    ("Numpad4", VirtualKeyCode::Numpad4), ("Numpad5", VirtualKeyCode::Numpad5),
+    ("Numpad6", VirtualKeyCode::Numpad6), ("Numpad7", VirtualKeyCode::Numpad7),
+    ("Numpad8", VirtualKeyCode::Numpad8), ("Numpad9", VirtualKeyCode::Numpad9),
+    ("Comma", VirtualKeyCode::Comma), ("Period", VirtualKeyCode::Period),
+    ("Slash", VirtualKeyCode::Slash), ("Semicolon", VirtualKeyCode::Semicolon),
+    ("Minus", VirtualKeyCode::Minus), ("Equals", VirtualKeyCode::Equals),
+    ("Tab", VirtualKeyCode::Tab),
+    ("LShift", VirtualKeyCode::LShift), ("RShift", VirtualKeyCode::RShift),
+    ("LControl", VirtualKeyCode::LControl), ("RControl", VirtualKeyCode::RControl),
+    ("LAlt", VirtualKeyCode::LMenu), ("RAlt", VirtualKeyCode::RMenu),
+    ("Apostrophe", VirtualKeyCode::Apostrophe), ("Backslash", VirtualKeyCode::Backslash),
+    ("Grave", VirtualKeyCode::Grave),
+    ("LBracket", VirtualKeyCode::LBracket), ("RBracket", VirtualKeyCode::RBracket),
+];
+
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode>
+{
+    let name = name.to_lowercase();
+
+    KEYS.iter().find(|x| x.0.to_lowercase() == name).map(|x| x.1)
+}
+
+fn pad_button_from_name(name: &str) -> Option<u8>
+{
+    let lower = name.to_lowercase();
+
+    if lower.starts_with("pad")
+    {
+        lower[3..].parse().ok()
+    }
+    else
+    {
+        None
+    }
+}
+
+fn pad_axis_from_name(name: &str) -> Option<PadAxis>
+{
+    let lower = name.to_lowercase();
+
+    if !lower.starts_with("axis") || lower.len() < 6
+    {
+        return None;
+    }
+
+    let positive = match &lower[lower.len() - 1..]
+    {
+        "+" => true,
+        "-" => false,
+        _ => return None
+    };
+
+    lower[4..lower.len() - 1].parse().ok().map(|x| PadAxis::new(x, positive))
+}
+
+pub fn key_name(key: VirtualKeyCode) -> &'static str
+{
+    KEYS.iter().find(|x| x.1 == key).map(|x| x.0).unwrap_or("?")
+}
+
+/// Picks an action and waits for the key to bind to it. Any key but Escape,
+/// which cancels, can be bound; the menus read their keys directly rather
+/// than through the bindings, so the player can't lock themselves out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ControlsMenu
+{
+    selected: usize,
+    waiting: bool,
+}
+
+impl Default for ControlsMenu
+{
+    fn default() -> ControlsMenu
+    {
+        ControlsMenu::new()
+    }
+}
+
+impl ControlsMenu
+{
+    pub fn new() -> ControlsMenu
+    {
+        ControlsMenu
+        {
+            selected: 0,
+            waiting: false,
+        }
+    }
+
+    pub fn selected(&self) -> Action
+    {
+        ACTIONS[self.selected]
+    }
+
+    pub fn is_waiting(&self) -> bool
+    {
+        self.waiting
+    }
+
+    /// Binds a gamepad button to the selected action if the menu is waiting
+    /// for one
+    pub fn handle_pad_button(&mut self, button: u8, bindings: &mut Bindings)
+    {
+        if self.waiting
+        {
+            self.waiting = false;
+            bindings.set_pad(self.selected(), &[button]);
+        }
+    }
+
+    /// Binds one end of a gamepad axis to the selected action if the menu is
+    /// waiting for a button
+    pub fn handle_pad_axis(&mut self, axis: PadAxis, bindings: &mut Bindings)
+    {
+        if self.waiting
+        {
+            self.waiting = false;
+            bindings.set_pad_axes(self.selected(), &[axis]);
+        }
+    }
+
+    /// Handles a key press, returning false once the menu is closed
+    pub fn handle_key(&mut self, key: VirtualKeyCode, bindings: &mut Bindings) -> bool
+    {
+        if self.waiting
+        {
+            self.waiting = false;
+
+            if key != VirtualKeyCode::Escape
+            {
+                bindings.set(self.selected(), &[key]);
+            }
+
+            return true;
+        }
+
+        match key
+        {
+            VirtualKeyCode::Back | VirtualKeyCode::Delete =>
+            {
+                bindings.set(self.selected(), &[]);
+                bindings.set_pad(self.selected(), &[]);
+                bindings.set_pad_axes(self.selected(), &[]);
+
+                true
+            },
+
+            _ => match MenuInput::from_key(key)
+            {
+                Some(x) => self.handle_input(x),
+                None => true
+            }
+        }
+    }
+
+    /// Moves through the menu, returning false once the menu is closed. This
+    /// is all a gamepad can do here, so it is ignored while waiting for the
+    /// input to bind.
+    pub fn handle_input(&mut self, input: MenuInput) -> bool
+    {
+        if self.waiting
+        {
+            return true;
+        }
+
+        match input
+        {
+            MenuInput::Up =>
+                self.selected = (self.selected + ACTIONS.len() - 1) % ACTIONS.len(),
+
+            MenuInput::Down =>
+                self.selected = (self.selected + 1) % ACTIONS.len(),
+
+            MenuInput::Confirm =>
+                self.waiting = true,
+
+            MenuInput::Back =>
+                return false,
+
+            MenuInput::Left | MenuInput::Right => ()
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use glium::glutin::VirtualKeyCode;
+
+    use input::Button;
+    use scene::MenuInput;
+    use super::{Action, Bindings, ControlsMenu, PadAxis, ACTIONS};
+
+    #[test]
+    fn round_trip()
+    {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::HardDrop, &[VirtualKeyCode::Up, VirtualKeyCode::Numpad8]);
+        bindings.set_pad(Action::HardDrop, &[11, 3]);
+        bindings.set_pad_axes(Action::Hold, &[PadAxis::new(2, true)]);
+        bindings.set_pad_axes(Action::MoveLeft, &[]);
+        bindings.deadzone = 0.25;
+
+        assert_eq!(Bindings::parse(&bindings.to_string()), Ok(bindings));
+        assert!(Bindings::parse("spin = Q").is_err());
+        assert!(Bindings::parse("hold = Nope").is_err());
+        assert!(Bindings::parse("deadzone = 2").is_err());
+        assert!(Bindings::parse("hold = Axis2").is_err());
+    }
+
+    #[test]
+    fn keys_only_lines_keep_pad_bindings()
+    {
+        let defaults = Bindings::default();
+        let bindings = Bindings::parse("hold = C\nmove_left = J\nhard_drop = Space, Axis3-")
+            .unwrap();
+
+        assert_eq!(bindings.keys(Action::Hold), &[VirtualKeyCode::C]);
+        assert_eq!(bindings.pad_buttons(Action::Hold), defaults.pad_buttons(Action::Hold));
+        assert_eq!(bindings.pad_axes(Action::MoveLeft), defaults.pad_axes(Action::MoveLeft));
+        assert_eq!(bindings.pad_axes(Action::HardDrop), &[PadAxis::new(3, false)]);
+        assert_eq!(bindings.axis_action(PadAxis::new(7, false)), None);
+    }
+
+    #[test]
+    fn keys_belong_to_one_action()
+    {
+        let bindings = Bindings::parse("hard_drop = up, pad3\nhold =\ndeadzone = 0.5").unwrap();
+
+        assert_eq!(bindings.action(VirtualKeyCode::Up), Some(Action::HardDrop));
+        assert_eq!(bindings.keys(Action::RotateCW), &[VirtualKeyCode::Z]);
+        assert!(bindings.keys(Action::Hold).is_empty());
+        assert!(bindings.pad_buttons(Action::Hold).is_empty());
+        assert_eq!(bindings.pad_action(3), Some(Action::HardDrop));
+
+        let frame = bindings.frame(&[VirtualKeyCode::Up, VirtualKeyCode::Left]);
+        assert!(frame.is_held(Button::HardDrop));
+        assert!(frame.is_held(Button::Left));
+        assert!(!frame.is_held(Button::RotateRight));
+    }
+
+    #[test]
+    fn controls_menu_works_without_a_keyboard()
+    {
+        let mut bindings = Bindings::default();
+        let mut menu = ControlsMenu::new();
+
+        assert!(menu.handle_input(MenuInput::Up));
+        assert_eq!(menu.selected(), ACTIONS[ACTIONS.len() - 1]);
+
+        assert!(menu.handle_input(MenuInput::Down));
+        assert!(menu.handle_input(MenuInput::Down));
+        assert_eq!(menu.selected(), ACTIONS[1]);
+
+        // Once waiting, the next pad button is the binding, not a command
+        assert!(menu.handle_input(MenuInput::Confirm));
+        assert!(menu.handle_input(MenuInput::Back));
+        assert!(menu.is_waiting());
+
+        menu.handle_pad_button(9, &mut bindings);
+        assert_eq!(bindings.pad_buttons(ACTIONS[1]), &[9]);
+
+        assert!(!menu.handle_input(MenuInput::Back));
+    }
+}
@@ -1,13 +1,20 @@
 use cgmath::Vector2;
 
-#[derive(Copy, Clone)]
+use tetromino::Shape;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// A cell of a matrix. Occupied cells remember which shape filled them so
+/// the stack keeps its colors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Cell
 {
-    Occupied,
+    Occupied(Shape),
     Unoccupied
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CellMatrix
 {
     pub width: u8,
@@ -97,21 +104,12 @@ impl CellMatrix
             {
                 match self.get_cell(x, y)
                 {
-                    Cell::Occupied =>
+                    Cell::Occupied(_) =>
                     {
                         let pos = Vector2::new(x as i8 + offset.x,
                             y as i8 + offset.y);
 
-                        if  pos.x < board.width as i8 && pos.x >= 0 &&
-                            pos.y < board.height as i8 && pos.y >= 0
-                        {
-                            match board.get_cell(pos.x as u8, pos.y as u8)
-                            {
-                                Cell::Occupied => return true,
-                                _ => ()
-                            }
-                        }
-                        else
+                        if board.is_blocked(pos)
                         {
                             return true
                         }
@@ -123,4 +121,137 @@ impl CellMatrix
 
         false
     }
+
+    /// How many rows this matrix can fall from `offset` before it would
+    /// collide with the board
+    pub fn drop_distance(&self, board: &CellMatrix, offset: Vector2<i8>) -> u8
+    {
+        let mut distance = 0;
+
+        while !self.collides(board, offset + Vector2::new(0, distance as i8 + 1))
+        {
+            distance += 1;
+        }
+
+        distance
+    }
+
+    /// Whether a cell is occupied or lies outside of the matrix
+    pub fn is_blocked(&self, pos: Vector2<i8>) -> bool
+    {
+        if  pos.x < self.width as i8 && pos.x >= 0 &&
+            pos.y < self.height as i8 && pos.y >= 0
+        {
+            match self.get_cell(pos.x as u8, pos.y as u8)
+            {
+                Cell::Occupied(_) => true,
+                _ => false
+            }
+        }
+        else
+        {
+            true
+        }
+    }
+
+    /// Writes the occupied cells of `piece` into this matrix at `offset`
+    pub fn place(&mut self, piece: &CellMatrix, offset: Vector2<i8>)
+    {
+        for y in 0..piece.height
+        {
+            for x in 0..piece.width
+            {
+                match piece.get_cell(x, y)
+                {
+                    cell @ Cell::Occupied(_) =>
+                    {
+                        let pos = Vector2::new(x as i8 + offset.x,
+                            y as i8 + offset.y);
+
+                        if  pos.x < self.width as i8 && pos.x >= 0 &&
+                            pos.y < self.height as i8 && pos.y >= 0
+                        {
+                            self.set_cell(pos.x as u8, pos.y as u8, cell);
+                        }
+                    }
+                    _ => ()
+                }
+            }
+        }
+    }
+
+    /// A 64-bit FNV-1a hash of the cells, for comparing boards cheaply
+    pub fn hash(&self) -> u64
+    {
+        let mut hash = FNV_OFFSET;
+
+        for row in self.matrix.iter()
+        {
+            for cell in row.iter()
+            {
+                let byte = match *cell
+                {
+                    Cell::Occupied(shape) => shape as u8 + 1,
+                    Cell::Unoccupied => 0
+                };
+
+                hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+            }
+        }
+
+        hash
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.matrix.iter().all(|row| row.iter().all(|cell|
+            match *cell
+            {
+                Cell::Occupied(_) => false,
+                Cell::Unoccupied => true
+            }))
+    }
+
+    pub fn is_row_full(&self, y: u8) -> bool
+    {
+        self.matrix[y as usize].iter().all(|cell|
+            match *cell
+            {
+                Cell::Occupied(_) => true,
+                Cell::Unoccupied => false
+            })
+    }
+
+    /// Removes a row and shifts every row above it down by one
+    pub fn remove_row(&mut self, y: u8)
+    {
+        self.matrix.remove(y as usize);
+
+        let mut row = Vec::new();
+
+        for _ in 0..self.width
+        {
+            row.push(Cell::Unoccupied);
+        }
+
+        self.matrix.insert(0, row);
+    }
+
+    /// Removes every full row, returning the indices of the cleared rows
+    /// from top to bottom
+    pub fn clear_full_rows(&mut self) -> Vec<u8>
+    {
+        let mut cleared = Vec::new();
+
+        for y in 0..self.height
+        {
+            if self.is_row_full(y)
+            {
+                self.remove_row(y);
+                cleared.push(y);
+            }
+        }
+
+        cleared
+    }
 }
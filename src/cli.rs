@@ -0,0 +1,196 @@
+use tetris::GameMode;
+use tetris::config::CONFIG_FILE;
+
+pub const USAGE: &'static str = "\
+usage: tetris [options] [replay]
+       tetris verify <replay>
+
+options:
+  -h, --help            show this help
+  -m, --mode <mode>     marathon, sprint or ultra
+  -s, --seed <n>        seed for the piece randomizer
+  -c, --config <file>   read settings from <file> instead of config.toml
+  -r, --replay <file>   play back a replay
+      --headless        run without a window and print the result. A replay
+                        is checked against its checkpoints; otherwise a game
+                        is played with no input until it ends.
+      --scale <n>       draw at <n> times the normal size, from 1 to 8
+      --fullscreen      fill the primary monitor
+";
+
+const MAX_SCALE: u32 = 8;
+
+/// What was asked for on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options
+{
+    pub help: bool,
+
+    pub mode: Option<GameMode>,
+    pub seed: Option<u64>,
+    pub config: String,
+    pub replay: Option<String>,
+
+    pub headless: bool,
+    pub scale: Option<u32>,
+    pub fullscreen: bool,
+}
+
+impl Default for Options
+{
+    fn default() -> Options
+    {
+        Options
+        {
+            help: false,
+
+            mode: None,
+            seed: None,
+            config: CONFIG_FILE.to_string(),
+            replay: None,
+
+            headless: false,
+            scale: None,
+            fullscreen: false,
+        }
+    }
+}
+
+/// Reads the arguments after the program name
+pub fn parse(args: &[String]) -> Result<Options, String>
+{
+    let mut options = Options::default();
+    let mut args = args.iter();
+    let mut positional = vec![];
+
+    while let Some(arg) = args.next()
+    {
+        match &arg[..]
+        {
+            "-h" | "--help" => options.help = true,
+
+            "-m" | "--mode" =>
+            {
+                let value = try!(value(arg, args.next()));
+
+                options.mode = match GameMode::from_name(value)
+                {
+                    Some(x) => Some(x),
+                    None => return Err(format!("unknown mode `{}`", value))
+                };
+            },
+
+            "-s" | "--seed" =>
+            {
+                let value = try!(value(arg, args.next()));
+
+                options.seed = match value.parse()
+                {
+                    Ok(x) => Some(x),
+                    Err(_) => return Err(format!("seed must be a whole number, not `{}`", value))
+                };
+            },
+
+            "-c" | "--config" => options.config = try!(value(arg, args.next())).to_string(),
+            "-r" | "--replay" => options.replay = Some(try!(value(arg, args.next())).to_string()),
+
+            "--headless" => options.headless = true,
+
+            "--scale" =>
+            {
+                let value = try!(value(arg, args.next()));
+
+                options.scale = match value.parse()
+                {
+                    Ok(x) if x >= 1 && x <= MAX_SCALE => Some(x),
+                    _ => return Err(format!("scale must be from 1 to {}, not `{}`",
+                        MAX_SCALE, value))
+                };
+            },
+
+            "--fullscreen" => options.fullscreen = true,
+
+            _ if arg.starts_with("-") => return Err(format!("unknown option `{}`", arg)),
+
+            _ => positional.push(arg)
+        }
+    }
+
+    // `verify <replay>` is short for `--headless --replay <replay>`
+    if positional.len() > 0 && *positional[0] == "verify"
+    {
+        positional.remove(0);
+        options.headless = true;
+
+        if positional.is_empty() && options.replay.is_none()
+        {
+            return Err("verify needs a replay".to_string());
+        }
+    }
+
+    match (positional.len(), &options.replay)
+    {
+        (0, _) => (),
+        (1, &None) => options.replay = Some(positional[0].to_string()),
+        _ => return Err("too many arguments".to_string())
+    }
+
+    if options.replay.is_some() && (options.mode.is_some() || options.seed.is_some())
+    {
+        return Err("a replay brings its own mode and seed".to_string());
+    }
+
+    Ok(options)
+}
+
+fn value<'a>(option: &str, value: Option<&'a String>) -> Result<&'a str, String>
+{
+    match value
+    {
+        Some(x) => Ok(x),
+        None => Err(format!("{} needs a value", option))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use tetris::GameMode;
+    use super::parse;
+
+    fn args(text: &str) -> Vec<String>
+    {
+        text.split(' ').filter(|x| !x.is_empty()).map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn reads_options()
+    {
+        let options = parse(&args("-m sprint --seed 42 --scale 2 --fullscreen -c my.toml"))
+            .unwrap();
+
+        assert_eq!(options.mode, Some(GameMode::Sprint));
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.scale, Some(2));
+        assert!(options.fullscreen);
+        assert_eq!(options.config, "my.toml");
+        assert!(!options.headless);
+
+        let options = parse(&args("verify game.rtr")).unwrap();
+        assert!(options.headless);
+        assert_eq!(options.replay, Some("game.rtr".to_string()));
+
+        assert_eq!(parse(&args("old.rtr")).unwrap().replay, Some("old.rtr".to_string()));
+        assert!(parse(&args("--help")).unwrap().help);
+    }
+
+    #[test]
+    fn rejects_bad_arguments()
+    {
+        for text in ["--mode", "--mode zen", "--seed x", "--scale 9", "--bogus", "a.rtr b.rtr",
+            "verify", "--seed 1 a.rtr"].iter()
+        {
+            assert!(parse(&args(text)).is_err(), "{} was accepted", text);
+        }
+    }
+}
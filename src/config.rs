@@ -0,0 +1,509 @@
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use randomizer::RandomizerKind;
+use rotation::RotationKind;
+use rules::{GameMode, LockReset, Rules, MAX_PREVIEW};
+use scoring::ScoringKind;
+use tetris::TICKS_PER_SECOND;
+
+/// Where the config is read from unless another path is given. Despite the
+/// name only a TOML-like subset is understood, see `Config::parse`.
+pub const CONFIG_FILE: &'static str = "config.toml";
+
+/// Settings read at startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config
+{
+    /// Rules for new games. Replays bring their own.
+    pub rules: Rules,
+
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    /// Most times a second the window is redrawn. The game itself always
+    /// runs at `TICKS_PER_SECOND`, so faster rates draw some ticks twice.
+    pub max_frame_rate: u32,
+}
+
+/// Why a config couldn't be loaded
+#[derive(Debug)]
+pub enum ConfigError
+{
+    Io(io::Error),
+    Parse
+    {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            ConfigError::Io(ref e) => write!(f, "{}", e),
+            ConfigError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl error::Error for ConfigError
+{
+    fn description(&self) -> &str
+    {
+        match *self
+        {
+            ConfigError::Io(ref e) => e.description(),
+            ConfigError::Parse { ref message, .. } => message,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError
+{
+    fn from(e: io::Error) -> ConfigError
+    {
+        ConfigError::Io(e)
+    }
+}
+
+/// A value on the right of `key = value`
+#[derive(Debug, Clone, PartialEq)]
+enum Value
+{
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+/// One `key = value` line and the section it was found in
+struct Entry
+{
+    line: usize,
+    section: String,
+    key: String,
+    value: Value,
+}
+
+impl Default for Config
+{
+    fn default() -> Config
+    {
+        Config
+        {
+            rules: Rules::guideline(),
+
+            width: 496,
+            height: 376,
+            fullscreen: false,
+            max_frame_rate: TICKS_PER_SECOND,
+        }
+    }
+}
+
+impl Config
+{
+    /// Loads `path`, falling back to the defaults if it doesn't exist
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError>
+    {
+        let mut text = String::new();
+
+        match File::open(path)
+        {
+            Ok(mut file) => { try!(file.read_to_string(&mut text)); },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(ConfigError::Io(e)),
+        }
+
+        Config::parse(&text)
+    }
+
+    /// Reads a TOML-like subset: bare `[section]` headers and `key = value`
+    /// lines, where a value is a double quoted string, a number or a boolean.
+    /// Single quoted and multi-line strings, escapes, arrays, inline tables
+    /// and dotted keys are all rejected rather than misread.
+    ///
+    /// ```toml
+    /// [rules]
+    /// preset = "guideline"    # or "arcade", "classic"
+    /// mode = "marathon"       # or "sprint", "ultra"
+    /// rotation = "srs"        # or "ars", "nes"
+    /// randomizer = "seven_bag"
+    /// preview_count = 3
+    /// hold_enabled = true
+    /// lock_delay = 30
+    ///
+    /// [handling]
+    /// das = 10
+    /// arr = 2
+    /// soft_drop_factor = 20.0
+    ///
+    /// [video]
+    /// width = 496
+    /// height = 376
+    /// fullscreen = false
+    /// max_frame_rate = 60
+    /// ```
+    ///
+    /// The preset is applied before the other rules wherever it appears.
+    /// Anything left out keeps its default.
+    pub fn parse(text: &str) -> Result<Config, ConfigError>
+    {
+        let entries = try!(parse_entries(text));
+        let mut config = Config::default();
+
+        for entry in entries.iter().filter(|x| x.section == "rules" && x.key == "preset")
+        {
+            config.rules = match &try!(entry.string())[..]
+            {
+                "guideline" => Rules::guideline(),
+                "arcade" => Rules::arcade(),
+                "classic" => Rules::classic(),
+                _ => return Err(entry.error("preset must be guideline, arcade or classic")),
+            };
+        }
+
+        for entry in entries.iter()
+        {
+            match (&entry.section[..], &entry.key[..])
+            {
+                ("rules", "preset") => (),
+
+                ("rules", "mode") =>
+                    config.rules.mode = match GameMode::from_name(&try!(entry.string()))
+                    {
+                        Some(x) => x,
+                        None => return Err(entry.error("mode must be marathon, sprint or ultra")),
+                    },
+
+                ("rules", "rotation") =>
+                    config.rules.rotation = match &try!(entry.string())[..]
+                    {
+                        "srs" => RotationKind::Srs,
+                        "ars" => RotationKind::Ars,
+                        "nes" => RotationKind::Nes,
+                        _ => return Err(entry.error("rotation must be srs, ars or nes")),
+                    },
+
+                ("rules", "randomizer") =>
+                    config.rules.randomizer = match &try!(entry.string())[..]
+                    {
+                        "seven_bag" => RandomizerKind::SevenBag,
+                        "pure_random" => RandomizerKind::PureRandom,
+                        "history" => RandomizerKind::History,
+                        "nes" => RandomizerKind::Nes,
+                        _ => return Err(entry.error(
+                            "randomizer must be seven_bag, pure_random, history or nes")),
+                    },
+
+                ("rules", "preview_count") =>
+                    config.rules.preview_count = try!(entry.int(0, MAX_PREVIEW as i64)) as usize,
+
+                ("rules", "hold_enabled") => config.rules.hold_enabled = try!(entry.bool()),
+
+                ("rules", "lock_delay") =>
+                    config.rules.lock_delay = try!(entry.int(0, 600)) as u32,
+
+                ("rules", "lock_reset") =>
+                    config.rules.lock_reset = match &try!(entry.string())[..]
+                    {
+                        "extended" => LockReset::Extended,
+                        "infinite" => LockReset::Infinite,
+                        "step" => LockReset::Step,
+                        _ => return Err(entry.error(
+                            "lock_reset must be extended, infinite or step")),
+                    },
+
+                ("rules", "scoring") =>
+                    config.rules.scoring = match &try!(entry.string())[..]
+                    {
+                        "guideline" => ScoringKind::Guideline,
+                        "nes" => ScoringKind::Nes,
+                        _ => return Err(entry.error("scoring must be guideline or nes")),
+                    },
+
+                ("rules", "partial_lock_out") =>
+                    config.rules.partial_lock_out = try!(entry.bool()),
+
+                ("handling", "das") => config.rules.das = try!(entry.int(0, 600)) as u32,
+                ("handling", "arr") => config.rules.arr = try!(entry.int(0, 600)) as u32,
+
+                ("handling", "soft_drop_factor") =>
+                    config.rules.soft_drop_factor = try!(entry.float(1.0, 1000.0)) as f32,
+
+                ("video", "width") => config.width = try!(entry.int(1, 16384)) as u32,
+                ("video", "height") => config.height = try!(entry.int(1, 16384)) as u32,
+
+                ("video", "fullscreen") => config.fullscreen = try!(entry.bool()),
+
+                ("video", "max_frame_rate") =>
+                    config.max_frame_rate = try!(entry.int(1, 1000)) as u32,
+
+                _ => return Err(entry.error(
+                    &format!("unknown setting `{}` in [{}]", entry.key, entry.section))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl Entry
+{
+    fn error(&self, message: &str) -> ConfigError
+    {
+        ConfigError::Parse { line: self.line, message: message.to_string() }
+    }
+
+    fn string(&self) -> Result<String, ConfigError>
+    {
+        match self.value
+        {
+            Value::Str(ref x) => Ok(x.clone()),
+            _ => Err(self.error(&format!("{} must be a string", self.key))),
+        }
+    }
+
+    fn bool(&self) -> Result<bool, ConfigError>
+    {
+        match self.value
+        {
+            Value::Bool(x) => Ok(x),
+            _ => Err(self.error(&format!("{} must be true or false", self.key))),
+        }
+    }
+
+    fn int(&self, min: i64, max: i64) -> Result<i64, ConfigError>
+    {
+        match self.value
+        {
+            Value::Int(x) if x >= min && x <= max => Ok(x),
+            _ => Err(self.error(&format!("{} must be a whole number from {} to {}",
+                self.key, min, max))),
+        }
+    }
+
+    /// Whole numbers are accepted where a float is expected
+    fn float(&self, min: f64, max: f64) -> Result<f64, ConfigError>
+    {
+        let value = match self.value
+        {
+            Value::Float(x) => x,
+            Value::Int(x) => x as f64,
+            _ => min - 1.0,
+        };
+
+        if value >= min && value <= max
+        {
+            Ok(value)
+        }
+        else
+        {
+            Err(self.error(&format!("{} must be a number from {} to {}", self.key, min, max)))
+        }
+    }
+}
+
+fn parse_entries(text: &str) -> Result<Vec<Entry>, ConfigError>
+{
+    let mut entries = vec![];
+    let mut section = String::new();
+
+    for (i, line) in text.lines().enumerate()
+    {
+        let line = strip_comment(line).trim();
+        let error = |message: &str|
+            ConfigError::Parse { line: i + 1, message: message.to_string() };
+
+        if line.is_empty()
+        {
+            continue;
+        }
+
+        if line.starts_with("[")
+        {
+            if !line.ends_with("]")
+            {
+                return Err(error("expected `]` after the section name"));
+            }
+
+            section = line[1..line.len() - 1].trim().to_string();
+
+            if !is_bare(&section)
+            {
+                return Err(error("expected a plain section name; dotted and array \
+                    tables aren't supported"));
+            }
+
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap().trim();
+
+        let value = match parts.next()
+        {
+            Some(x) => try!(parse_value(x.trim()).map_err(&error)),
+            None => return Err(error("expected `key = value`")),
+        };
+
+        if !is_bare(key)
+        {
+            return Err(error("expected a plain key; quoted and dotted keys aren't supported"));
+        }
+
+        entries.push(Entry
+        {
+            line: i + 1,
+            section: section.clone(),
+            key: key.to_string(),
+            value: value,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Cuts a line at the first `#` that isn't inside a string
+fn strip_comment(line: &str) -> &str
+{
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices()
+    {
+        match c
+        {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => ()
+        }
+    }
+
+    line
+}
+
+/// Whether a key or section name is made of letters, digits, `_` and `-`
+fn is_bare(name: &str) -> bool
+{
+    !name.is_empty() && name.chars().all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '-')
+}
+
+fn parse_value(text: &str) -> Result<Value, &'static str>
+{
+    if text.len() >= 2 && text.starts_with("\"") && text.ends_with("\"")
+    {
+        let inner = &text[1..text.len() - 1];
+
+        if inner.contains('"') || inner.contains('\\')
+        {
+            return Err("escapes and multi-line strings aren't supported");
+        }
+
+        return Ok(Value::Str(inner.to_string()));
+    }
+
+    match text
+    {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => ()
+    }
+
+    let digits: String = text.chars().filter(|x| *x != '_').collect();
+
+    match digits.parse::<i64>()
+    {
+        Ok(x) => Ok(Value::Int(x)),
+        Err(_) => digits.parse::<f64>().map(Value::Float)
+            .map_err(|_| "expected a double quoted string, number or boolean"),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use rotation::RotationKind;
+    use rules::Rules;
+    use super::{Config, ConfigError};
+
+    #[test]
+    fn reads_sections()
+    {
+        let config = Config::parse("
+            # Settings
+            [handling]
+            das = 8
+            soft_drop_factor = 40   # whole numbers are fine
+
+            [rules]
+            rotation = \"nes\"
+            preset = \"arcade\"
+
+            [video]
+            max_frame_rate = 144
+        ").unwrap();
+
+        let mut rules = Rules::arcade();
+        rules.das = 8;
+        rules.soft_drop_factor = 40.0;
+        rules.rotation = RotationKind::Nes;
+
+        assert_eq!(config.rules, rules);
+        assert_eq!(config.max_frame_rate, 144);
+        assert_eq!(config.width, Config::default().width);
+    }
+
+    #[test]
+    fn bad_values_name_the_line()
+    {
+        let errors = [
+            ("[rules]\n\npreview_count = 9", 3),
+            ("[handling]\narr = \"fast\"", 2),
+            ("[video]\nvsync = true", 2),
+            ("das = 10", 1),
+            ("[video\n", 1),
+        ];
+
+        for &(text, line) in errors.iter()
+        {
+            match Config::parse(text)
+            {
+                Err(ConfigError::Parse { line: x, .. }) => assert_eq!(x, line),
+                x => panic!("{:?} parsed as {:?}", text, x),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_the_rest_of_toml()
+    {
+        let errors = [
+            ("[rules]\nrotation = 'nes'", 2),
+            ("[rules]\nrotation = \"n\\u0065s\"", 2),
+            ("[rules]\nrotation = \"\"\"nes\"\"\"", 2),
+            ("[handling]\ndas = [10, 12]", 2),
+            ("[handling]\ndas = { frames = 10 }", 2),
+            ("handling.das = 10", 1),
+            ("[handling]\n\"das\" = 10", 2),
+            ("[handling.keys]\ndas = 10", 1),
+            ("[[handling]]\ndas = 10", 1),
+        ];
+
+        for &(text, line) in errors.iter()
+        {
+            match Config::parse(text)
+            {
+                Err(ConfigError::Parse { line: x, .. }) => assert_eq!(x, line),
+                x => panic!("{:?} parsed as {:?}", text, x),
+            }
+        }
+    }
+}
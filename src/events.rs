@@ -0,0 +1,35 @@
+use scoring::LockResult;
+use tspin::TSpin;
+
+/// How the stack topped out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TopOut
+{
+    /// A new piece spawned overlapping the stack
+    BlockOut,
+    /// A piece locked entirely inside the hidden rows
+    LockOut,
+    /// A piece locked partly inside the hidden rows
+    PartialLockOut,
+}
+
+/// Things that happened during an update, for the UI to call out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent
+{
+    /// A T-spin locked, clearing `lines` lines
+    TSpin
+    {
+        kind: TSpin,
+        lines: u32,
+    },
+
+    /// A piece locked
+    Locked(LockResult),
+
+    /// The game ended
+    GameOver(TopOut),
+
+    /// The goal of the game mode was reached, ending the game
+    GoalReached,
+}
@@ -0,0 +1,129 @@
+use std::io;
+
+use cgmath::Vector2;
+
+use glium;
+
+use rect::Rect;
+use sprite::{DrawContext, Sprite};
+use spritemanager::Textures;
+
+/// Size of a glyph in `images/font.png`, in pixels
+pub const GLYPH_WIDTH: f32 = 5.0;
+pub const GLYPH_HEIGHT: f32 = 7.0;
+
+/// Distance from one character or line to the next at scale 1
+pub const ADVANCE: f32 = GLYPH_WIDTH + 1.0;
+pub const LINE_HEIGHT: f32 = GLYPH_HEIGHT + 3.0;
+
+/// The atlas holds ASCII from space to `~` in rows of 16, one 6x8 cell each
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+const ATLAS_COLUMNS: usize = 16;
+const ATLAS_WIDTH: f32 = 96.0;
+const ATLAS_HEIGHT: f32 = 48.0;
+const CELL_WIDTH: f32 = 6.0;
+const CELL_HEIGHT: f32 = 8.0;
+
+/// Where text is placed relative to its position
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align
+{
+    Left,
+    Center,
+    Right,
+}
+
+/// Draws text from a bitmap font, moving one sprite per glyph around
+pub struct Font
+{
+    /// Glyph sprites, indexed by character code minus `FIRST_CHAR`
+    glyphs: Vec<Sprite>,
+}
+
+impl Font
+{
+    pub fn new(display: &glium::backend::glutin_backend::GlutinFacade) -> io::Result<Font>
+    {
+        let mut glyphs = vec![];
+
+        for i in 0..(LAST_CHAR - FIRST_CHAR + 1) as usize
+        {
+            let x = (i % ATLAS_COLUMNS) as f32 * CELL_WIDTH;
+            let y = (i / ATLAS_COLUMNS) as f32 * CELL_HEIGHT;
+
+            glyphs.push(try!(Sprite::new_region(
+                display,
+                Textures::Font,
+                Rect::new(0.0, 0.0, GLYPH_WIDTH, GLYPH_HEIGHT),
+                Rect::new(x / ATLAS_WIDTH, y / ATLAS_HEIGHT,
+                    GLYPH_WIDTH / ATLAS_WIDTH, GLYPH_HEIGHT / ATLAS_HEIGHT),
+                Vector2::new(0.0, 0.0)
+            )));
+        }
+
+        Ok(Font
+        {
+            glyphs: glyphs,
+        })
+    }
+
+    /// Width of `text` in pixels
+    pub fn measure(text: &str, scale: f32) -> f32
+    {
+        match text.chars().count()
+        {
+            0 => 0.0,
+            x => (x as f32 * ADVANCE - 1.0) * scale
+        }
+    }
+
+    /// Draws one line of text with its top left corner at `position`
+    pub fn draw_text(&mut self, context: &mut DrawContext, position: Vector2<f32>, text: &str,
+        color: [f32; 4])
+    {
+        self.draw_text_aligned(context, position, text, color, 1.0, Align::Left);
+    }
+
+    /// Draws one line of text, `scale` times the size of the font. Characters
+    /// outside the atlas are drawn as `?`.
+    pub fn draw_text_aligned(&mut self, context: &mut DrawContext, position: Vector2<f32>,
+        text: &str, color: [f32; 4], scale: f32, align: Align)
+    {
+        let width = Font::measure(text, scale);
+
+        let left = match align
+        {
+            Align::Left => position.x,
+            Align::Center => position.x - width / 2.0,
+            Align::Right => position.x - width,
+        };
+
+        // Whole pixels keep the nearest filter from splitting texels
+        let mut x = left.round();
+        let y = position.y.round();
+
+        for c in text.chars()
+        {
+            let code = c as u32;
+
+            let index = if code >= FIRST_CHAR as u32 && code <= LAST_CHAR as u32
+            {
+                (code - FIRST_CHAR as u32) as usize
+            }
+            else
+            {
+                (b'?' - FIRST_CHAR) as usize
+            };
+
+            let glyph = &mut self.glyphs[index];
+
+            glyph.set_position(Vector2::new(x, y));
+            glyph.set_tint(color);
+            glyph.set_scale(scale);
+            context.draw(glyph);
+
+            x += ADVANCE * scale;
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use bindings::{Action, Bindings, PadAxis};
+use input::InputFrame;
+
+/// A raw event from a controller
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GamepadEvent
+{
+    Button
+    {
+        button: u8,
+        pressed: bool,
+    },
+
+    /// A stick or D-pad moved. `value` goes from -1 (left or up) to 1 (right
+    /// or down).
+    Axis
+    {
+        axis: u8,
+        value: f32,
+    },
+}
+
+/// Where gamepad events come from
+pub trait GamepadBackend
+{
+    /// Returns the events that arrived since the last call, without blocking
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+}
+
+/// Turns the raw events of a backend into actions, the same ones keys are
+/// bound to
+pub struct Gamepad
+{
+    backend: Box<dyn GamepadBackend>,
+
+    buttons_down: Vec<u8>,
+    /// The last value of each axis, indexed by axis number
+    axes: Vec<f32>,
+
+    /// Actions currently held through the pad
+    held: Vec<Action>,
+}
+
+impl Gamepad
+{
+    pub fn new(backend: Box<dyn GamepadBackend>) -> Gamepad
+    {
+        Gamepad
+        {
+            backend: backend,
+
+            buttons_down: vec![],
+            axes: vec![],
+
+            held: vec![],
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<GamepadEvent>
+    {
+        self.backend.poll()
+    }
+
+    /// Applies an event, returning the actions it pressed (true) or
+    /// released (false)
+    pub fn handle(&mut self, event: GamepadEvent, bindings: &Bindings) -> Vec<(Action, bool)>
+    {
+        match event
+        {
+            GamepadEvent::Button { button, pressed } =>
+            {
+                self.buttons_down.retain(|x| *x != button);
+
+                if pressed
+                {
+                    self.buttons_down.push(button);
+                }
+            },
+
+            GamepadEvent::Axis { axis, value } =>
+            {
+                while self.axes.len() <= axis as usize
+                {
+                    self.axes.push(0.0);
+                }
+
+                self.axes[axis as usize] = value;
+            }
+        }
+
+        let held = self.held_actions(bindings);
+        let mut changes = vec![];
+
+        for action in self.held.iter().filter(|x| !held.contains(x))
+        {
+            changes.push((*action, false));
+        }
+
+        for action in held.iter().filter(|x| !self.held.contains(x))
+        {
+            changes.push((*action, true));
+        }
+
+        self.held = held;
+        changes
+    }
+
+    /// The engine buttons held through the pad
+    pub fn frame(&self) -> InputFrame
+    {
+        let mut frame = InputFrame::empty();
+
+        for action in self.held.iter()
+        {
+            match action.button()
+            {
+                Some(button) => frame.set(button, true),
+                None => ()
+            }
+        }
+
+        frame
+    }
+
+    fn held_actions(&self, bindings: &Bindings) -> Vec<Action>
+    {
+        let mut held = vec![];
+
+        for button in self.buttons_down.iter()
+        {
+            match bindings.pad_action(*button)
+            {
+                Some(x) => held.push(x),
+                None => ()
+            }
+        }
+
+        for (axis, value) in self.axes.iter().enumerate()
+        {
+            let end = pushed_end(axis as u8, *value, bindings.deadzone);
+
+            match end.and_then(|x| bindings.axis_action(x))
+            {
+                Some(x) => held.push(x),
+                None => ()
+            }
+        }
+
+        held.sort_by(|a, b| (*a as u8).cmp(&(*b as u8)));
+        held.dedup();
+        held
+    }
+}
+
+/// The end of an axis pushed past the deadzone, if either is
+pub fn pushed_end(axis: u8, value: f32, deadzone: f32) -> Option<PadAxis>
+{
+    if value <= -deadzone
+    {
+        Some(PadAxis::new(axis, false))
+    }
+    else if value >= deadzone
+    {
+        Some(PadAxis::new(axis, true))
+    }
+    else
+    {
+        None
+    }
+}
+
+/// A backend fed by hand, for tests. Clones share the same queue, so a test
+/// can keep one to inject events into a `Gamepad` owning the other.
+#[derive(Clone)]
+pub struct MockGamepad
+{
+    events: Rc<RefCell<VecDeque<GamepadEvent>>>,
+}
+
+impl Default for MockGamepad
+{
+    fn default() -> MockGamepad
+    {
+        MockGamepad::new()
+    }
+}
+
+impl MockGamepad
+{
+    pub fn new() -> MockGamepad
+    {
+        MockGamepad
+        {
+            events: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn push(&self, event: GamepadEvent)
+    {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    pub fn button(&self, button: u8, pressed: bool)
+    {
+        self.push(GamepadEvent::Button { button: button, pressed: pressed });
+    }
+
+    pub fn axis(&self, axis: u8, value: f32)
+    {
+        self.push(GamepadEvent::Axis { axis: axis, value: value });
+    }
+}
+
+impl GamepadBackend for MockGamepad
+{
+    fn poll(&mut self) -> Vec<GamepadEvent>
+    {
+        let mut events = vec![];
+
+        while let Some(event) = self.events.borrow_mut().pop_front()
+        {
+            events.push(event);
+        }
+
+        events
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use self::linux::LinuxJoystick;
+
+#[cfg(target_os = "linux")]
+mod linux
+{
+    use std::fs::File;
+    use std::io;
+    use std::io::Read;
+    use std::path::Path;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+
+    use super::{GamepadBackend, GamepadEvent};
+
+    const JS_EVENT_BUTTON: u8 = 0x01;
+    const JS_EVENT_AXIS: u8 = 0x02;
+    /// Set on the synthetic events describing the initial state
+    const JS_EVENT_INIT: u8 = 0x80;
+
+    /// Reads the Linux joystick API (`/dev/input/js0`) on its own thread
+    pub struct LinuxJoystick
+    {
+        events: Receiver<GamepadEvent>,
+    }
+
+    impl LinuxJoystick
+    {
+        pub fn open<P: AsRef<Path>>(path: P) -> io::Result<LinuxJoystick>
+        {
+            let mut file = try!(File::open(path));
+            let (sender, receiver) = channel();
+
+            thread::spawn(move ||
+            {
+                // struct js_event { u32 time; i16 value; u8 type; u8 number; }
+                let mut buffer = [0u8; 8];
+
+                while read_full(&mut file, &mut buffer).is_ok()
+                {
+                    let value = (buffer[4] as u16 | (buffer[5] as u16) << 8) as i16;
+                    let number = buffer[7];
+
+                    let event = match buffer[6] & !JS_EVENT_INIT
+                    {
+                        JS_EVENT_BUTTON =>
+                            GamepadEvent::Button { button: number, pressed: value != 0 },
+
+                        JS_EVENT_AXIS =>
+                            GamepadEvent::Axis { axis: number, value: value as f32 / 32767.0 },
+
+                        _ => continue
+                    };
+
+                    if sender.send(event).is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+
+            Ok(LinuxJoystick { events: receiver })
+        }
+    }
+
+    impl GamepadBackend for LinuxJoystick
+    {
+        fn poll(&mut self) -> Vec<GamepadEvent>
+        {
+            let mut events = vec![];
+
+            while let Ok(event) = self.events.try_recv()
+            {
+                events.push(event);
+            }
+
+            events
+        }
+    }
+
+    fn read_full(file: &mut File, buffer: &mut [u8]) -> io::Result<()>
+    {
+        let mut read = 0;
+
+        while read < buffer.len()
+        {
+            match try!(file.read(&mut buffer[read..]))
+            {
+                0 => return Err(io::Error::new(io::ErrorKind::Other, "joystick closed")),
+                x => read += x
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bindings::{Action, Bindings, PadAxis};
+    use input::Button;
+    use super::{Gamepad, MockGamepad};
+
+    fn changes(pad: &mut Gamepad, bindings: &Bindings) -> Vec<(Action, bool)>
+    {
+        let mut changes = vec![];
+
+        for event in pad.poll()
+        {
+            changes.extend(pad.handle(event, bindings).into_iter());
+        }
+
+        changes
+    }
+
+    #[test]
+    fn mock_events_become_actions()
+    {
+        let bindings = Bindings::default();
+        let mock = MockGamepad::new();
+        let mut pad = Gamepad::new(Box::new(mock.clone()));
+
+        // Inside the deadzone nothing happens
+        mock.axis(0, -0.3);
+        assert_eq!(changes(&mut pad, &bindings), vec![]);
+
+        mock.axis(0, -0.9);
+        mock.button(3, true);
+        assert_eq!(changes(&mut pad, &bindings),
+            vec![(Action::MoveLeft, true), (Action::Hold, true)]);
+
+        let frame = pad.frame();
+        assert!(frame.is_held(Button::Left));
+        assert!(frame.is_held(Button::Hold));
+
+        // The D-pad holding the same direction keeps it held
+        mock.axis(6, -1.0);
+        mock.axis(0, 0.0);
+        assert_eq!(changes(&mut pad, &bindings), vec![]);
+
+        mock.axis(6, 0.0);
+        mock.button(3, false);
+        mock.button(7, true);
+        assert_eq!(changes(&mut pad, &bindings),
+            vec![(Action::MoveLeft, false), (Action::Hold, false), (Action::Pause, true)]);
+    }
+
+    #[test]
+    fn axes_follow_the_bindings()
+    {
+        let mut bindings = Bindings::default();
+        bindings.set_pad_axes(Action::Hold, &[PadAxis::new(2, true)]);
+        bindings.set_pad_axes(Action::MoveLeft, &[]);
+
+        let mock = MockGamepad::new();
+        let mut pad = Gamepad::new(Box::new(mock.clone()));
+
+        mock.axis(0, -1.0);
+        mock.axis(2, 1.0);
+        assert_eq!(changes(&mut pad, &bindings), vec![(Action::Hold, true)]);
+
+        mock.axis(2, -1.0);
+        assert_eq!(changes(&mut pad, &bindings), vec![(Action::Hold, false)]);
+    }
+}
@@ -0,0 +1,106 @@
+use clock_ticks;
+
+use tetris::{InputFrame, Replay, Rules, Score, Tetris};
+use tetris::tetris::TICKS_PER_SECOND;
+
+/// Runs a replay without a window and prints how it ended. Returns 1 if it
+/// doesn't match its checkpoints.
+pub fn verify(replay: &Replay) -> i32
+{
+    let result = replay.verify();
+
+    print_summary(&result.score, result.pieces, result.ticks, result.frames, result.board_hash);
+
+    match result.divergence
+    {
+        None =>
+        {
+            println!("checkpoints: {} ok", result.checkpoints);
+            0
+        },
+
+        Some(x) =>
+        {
+            println!("checkpoints: diverged at frame {} \
+                (expected score {}, lines {}, board {:016x})",
+                x.frame, x.score, x.lines, x.board_hash);
+            1
+        }
+    }
+}
+
+/// Plays a game with no input until it ends, for timing the engine
+pub fn simulate(rules: Rules, seed: u64) -> i32
+{
+    let mode = rules.mode;
+    let mut tetris = Tetris::new(rules, seed);
+    let mut frames = 0;
+
+    let start = clock_ticks::precise_time_ns();
+
+    while !tetris.is_game_over()
+    {
+        tetris.step(InputFrame::empty());
+        frames += 1;
+    }
+
+    let elapsed = clock_ticks::precise_time_ns() - start;
+
+    println!("seed:        {}", seed);
+    println!("mode:        {}", mode.name());
+    print_summary(tetris.score(), tetris.pieces(), tetris.ticks(), frames, tetris.board().hash());
+    println!("result:      {}",
+        if tetris.is_goal_reached() { "goal reached" } else { "topped out" });
+    println!("simulated:   {} frames in {:.3} ms", frames, elapsed as f64 / 1E+6);
+
+    0
+}
+
+fn print_summary(score: &Score, pieces: u32, ticks: u32, frames: u32, board_hash: u64)
+{
+    let centiseconds = ticks as u64 * 100 / TICKS_PER_SECOND as u64;
+
+    println!("score:       {}", score.score);
+    println!("lines:       {}", score.lines);
+    println!("level:       {}", score.level);
+    println!("pieces:      {}", pieces);
+    println!("time:        {}:{:02}.{:02} ({} frames)", centiseconds / 6000,
+        centiseconds / 100 % 60, centiseconds % 100, frames);
+    println!("board hash:  {:016x}", board_hash);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use tetris::{Button, InputFrame, Replay, Rules, Tetris};
+    use super::verify;
+
+    #[test]
+    fn corrupted_checkpoints_fail()
+    {
+        let rules = Rules::guideline();
+        let mut tetris = Tetris::new(rules.clone(), 7);
+        let mut replay = Replay::new(rules, 7);
+
+        for i in 0..1500u32
+        {
+            let frame = if i % 40 == 0
+            {
+                InputFrame::empty().with(Button::HardDrop)
+            }
+            else
+            {
+                InputFrame::empty()
+            };
+
+            tetris.step(frame);
+            replay.record(frame, &tetris);
+        }
+
+        replay.finish(&tetris);
+        assert_eq!(verify(&replay), 0);
+
+        replay.checkpoints[1].board_hash ^= 1;
+        assert_eq!(verify(&replay), 1);
+    }
+}
@@ -0,0 +1,198 @@
+/// The buttons the engine understands. Replays store them as bits in this
+/// order, so only ever append.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Button
+{
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateRight,
+    RotateLeft,
+    Hold,
+    Rotate180,
+}
+
+pub const BUTTONS: [Button; 8] = [
+    Button::Left,
+    Button::Right,
+    Button::SoftDrop,
+    Button::HardDrop,
+    Button::RotateRight,
+    Button::RotateLeft,
+    Button::Hold,
+    Button::Rotate180,
+];
+
+impl Button
+{
+    fn bit(&self) -> u8
+    {
+        1 << (*self as u8)
+    }
+}
+
+/// The buttons held during one tick of the game. A game is fully decided by
+/// its rules, its seed and the frames fed to `Tetris::step`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InputFrame
+{
+    bits: u8,
+}
+
+impl InputFrame
+{
+    pub fn empty() -> InputFrame
+    {
+        InputFrame { bits: 0 }
+    }
+
+    /// Builds a frame from `bits()`, dropping bits that aren't buttons
+    pub fn from_bits(bits: u8) -> InputFrame
+    {
+        let mask = BUTTONS.iter().fold(0, |mask, x| mask | x.bit());
+
+        InputFrame { bits: bits & mask }
+    }
+
+    pub fn bits(&self) -> u8
+    {
+        self.bits
+    }
+
+    pub fn is_held(&self, button: Button) -> bool
+    {
+        self.bits & button.bit() != 0
+    }
+
+    pub fn set(&mut self, button: Button, held: bool)
+    {
+        if held
+        {
+            self.bits |= button.bit();
+        }
+        else
+        {
+            self.bits &= !button.bit();
+        }
+    }
+
+    /// The same frame with `button` held as well
+    pub fn with(mut self, button: Button) -> InputFrame
+    {
+        self.set(button, true);
+        self
+    }
+
+    /// Buttons held in either frame
+    pub fn union(&self, other: InputFrame) -> InputFrame
+    {
+        InputFrame { bits: self.bits | other.bits }
+    }
+
+    /// Buttons held in this frame that weren't held in `previous`
+    pub fn pressed_since(&self, previous: InputFrame) -> InputFrame
+    {
+        InputFrame { bits: self.bits & !previous.bits }
+    }
+}
+
+/// Holds back buttons that were already down when play started or resumed
+/// until they are let go, so the key that confirmed a menu doesn't also act
+/// in game
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InputMask
+{
+    masked: InputFrame,
+}
+
+impl Default for InputMask
+{
+    fn default() -> InputMask
+    {
+        InputMask::new()
+    }
+}
+
+impl InputMask
+{
+    pub fn new() -> InputMask
+    {
+        InputMask { masked: InputFrame::empty() }
+    }
+
+    /// Masks every button in `held`
+    pub fn mask(&mut self, held: InputFrame)
+    {
+        self.masked = held;
+    }
+
+    /// Unmasks the buttons no longer held, then takes the rest out of `held`
+    pub fn apply(&mut self, held: InputFrame) -> InputFrame
+    {
+        self.masked = InputFrame { bits: self.masked.bits & held.bits };
+
+        InputFrame { bits: held.bits & !self.masked.bits }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use rotation::Orientation;
+    use rules::Rules;
+    use tetris::Tetris;
+    use super::{Button, InputFrame, InputMask, BUTTONS};
+
+    #[test]
+    fn bits_round_trip()
+    {
+        let mut frame = InputFrame::empty();
+
+        for button in BUTTONS.iter()
+        {
+            frame.set(*button, true);
+            assert_eq!(InputFrame::from_bits(frame.bits()), frame);
+        }
+
+        assert_eq!(InputFrame::from_bits(0xff), frame);
+    }
+
+    #[test]
+    fn presses_are_edges()
+    {
+        let previous = InputFrame::empty().with(Button::Left);
+        let frame = previous.with(Button::HardDrop);
+        let pressed = frame.pressed_since(previous);
+
+        assert!(pressed.is_held(Button::HardDrop));
+        assert!(!pressed.is_held(Button::Left));
+        assert_eq!(previous.pressed_since(frame), InputFrame::empty());
+    }
+
+    #[test]
+    fn resuming_with_confirm_held_does_not_rotate()
+    {
+        let mut tetris = Tetris::new(Rules::guideline(), 1);
+        let mut mask = InputMask::new();
+        let orientation = |x: &Tetris| x.current().unwrap().orientation;
+
+        // Rotate is also the menu's confirm, and is still down on resuming
+        let confirm = InputFrame::empty().with(Button::RotateRight);
+        mask.mask(confirm);
+
+        tetris.step(mask.apply(confirm));
+        tetris.step(mask.apply(confirm));
+        assert_eq!(orientation(&tetris), Orientation::Spawn);
+
+        // Other buttons still work while it is held
+        let column = tetris.current().unwrap().cell_position.x;
+        tetris.step(mask.apply(confirm.with(Button::Left)));
+        assert_eq!(tetris.current().unwrap().cell_position.x, column - 1);
+
+        // Once let go it rotates as usual
+        tetris.step(mask.apply(InputFrame::empty()));
+        tetris.step(mask.apply(confirm));
+        assert_eq!(orientation(&tetris), Orientation::Right);
+    }
+}
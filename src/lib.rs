@@ -0,0 +1,55 @@
+//! The Rustris game engine. Everything needed to run a game lives here and
+//! works without a window; the `render` feature adds the glium front end.
+
+extern crate cgmath;
+#[cfg(feature = "render")]
+extern crate clock_ticks;
+#[cfg(feature = "render")]
+#[macro_use]
+extern crate glium;
+#[cfg(feature = "render")]
+extern crate image;
+
+pub mod cellmatrix;
+pub mod config;
+pub mod events;
+pub mod input;
+pub mod randomizer;
+pub mod replay;
+pub mod rng;
+pub mod rotation;
+pub mod rules;
+pub mod scoring;
+pub mod tetris;
+pub mod tetromino;
+pub mod tspin;
+
+//mod frametimer;
+#[cfg(feature = "render")]
+pub mod bindings;
+#[cfg(feature = "render")]
+mod font;
+#[cfg(feature = "render")]
+pub mod gamepad;
+#[cfg(feature = "render")]
+mod rect;
+#[cfg(feature = "render")]
+pub mod renderer;
+#[cfg(feature = "render")]
+pub mod rootwindow;
+#[cfg(feature = "render")]
+pub mod scene;
+#[cfg(feature = "render")]
+mod sprite;
+#[cfg(feature = "render")]
+mod spritemanager;
+
+pub use cellmatrix::{Cell, CellMatrix};
+pub use config::{Config, ConfigError};
+pub use events::{GameEvent, TopOut};
+pub use input::{Button, InputFrame};
+pub use replay::{Checkpoint, Playback, Replay, Verification};
+pub use rules::{GameMode, Rules};
+pub use scoring::{LockResult, Score};
+pub use tetris::{GameState, Tetris};
+pub use tetromino::{Shape, Tetromino};
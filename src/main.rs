@@ -1,26 +1,125 @@
 extern crate clock_ticks;
-#[macro_use]
-extern crate glium;
-extern crate cgmath;
-extern crate image;
+extern crate tetris;
 
-mod cellmatrix;
-//mod frametimer;
-mod rootwindow;
-mod rect;
-mod sprite;
-mod spritemanager;
-mod tetris;
-mod tetromino;
+mod cli;
+mod headless;
 
-use rootwindow::RootWindow;
+use std::env;
+use std::io;
+use std::io::Write;
+use std::process;
 
-const WIDTH: u32 = 400;
-const HEIGHT: u32 = 376;
+use tetris::{Config, Replay, Rules};
+#[cfg(feature = "render")]
+use tetris::renderer::{SCREEN_HEIGHT, SCREEN_WIDTH};
+#[cfg(feature = "render")]
+use tetris::rootwindow::RootWindow;
+
+use cli::USAGE;
 
 fn main()
 {
-    let mut rootwindow = RootWindow::new(WIDTH, HEIGHT)
-        .unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match cli::parse(&args)
+    {
+        Ok(x) => x,
+        Err(e) => fail(format!("{}\n\n{}", e, USAGE))
+    };
+
+    if options.help
+    {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let mut config = match Config::load(&options.config)
+    {
+        Ok(x) => x,
+        Err(e) => fail(format!("{}: {}", options.config, e))
+    };
+
+    match options.mode
+    {
+        Some(x) => config.rules.mode = x,
+        None => ()
+    }
+
+    // A replay is played back instead of starting a game
+    let replay = match options.replay
+    {
+        Some(ref path) => match Replay::load(path)
+        {
+            Ok(x) => Some(x),
+            Err(e) => fail(format!("{}: {}", path, e))
+        },
+
+        None => None
+    };
+
+    let (rules, seed) = match replay
+    {
+        Some(ref x) => (x.rules.clone(), x.seed),
+        None => (config.rules.clone(),
+            options.seed.unwrap_or_else(|| clock_ticks::precise_time_ns()))
+    };
+
+    if options.headless
+    {
+        process::exit(match replay
+        {
+            Some(ref x) => headless::verify(x),
+            None => headless::simulate(rules, seed)
+        });
+    }
+
+    run_window(config, &options, replay, rules, seed);
+}
+
+#[cfg(feature = "render")]
+fn run_window(mut config: Config, options: &cli::Options, replay: Option<Replay>, rules: Rules,
+    seed: u64)
+{
+    match options.scale
+    {
+        Some(x) =>
+        {
+            config.width = SCREEN_WIDTH * x;
+            config.height = SCREEN_HEIGHT * x;
+        },
+
+        None => ()
+    }
+
+    config.fullscreen = config.fullscreen || options.fullscreen;
+
+    let mut rootwindow = match RootWindow::new(&config, rules, seed)
+    {
+        Ok(x) => x,
+        Err(e) => fail(format!("{}", e))
+    };
+
+    // Asking for a mode or seed skips the title screen
+    match replay
+    {
+        Some(x) => rootwindow.play(x),
+        None if options.mode.is_some() || options.seed.is_some() => rootwindow.skip_title(),
+        None => ()
+    }
+
     rootwindow.start();
 }
+
+/// Without the `render` feature there is no window, only `--headless`
+#[cfg(not(feature = "render"))]
+fn run_window(_: Config, _: &cli::Options, _: Option<Replay>, _: Rules, _: u64)
+{
+    fail("built without the render feature; only --headless runs are available".to_string());
+}
+
+/// Prints an error to stderr, keeping stdout for the results of headless runs
+fn fail(message: String) -> !
+{
+    let _ = writeln!(io::stderr(), "{}", message);
+    process::exit(2);
+}
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+
+use rng::Rng;
+use tetromino::{Shape, SHAPES};
+
+/// Produces the sequence of pieces for a game
+pub trait Randomizer
+{
+    fn next(&mut self) -> Shape;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RandomizerKind
+{
+    /// Deals all seven pieces in a shuffled bag before refilling
+    SevenBag,
+    /// Every piece is independent
+    PureRandom,
+    /// TGM: rerolls pieces found in the last four dealt
+    History,
+    /// NES: rerolls a repeat of the previous piece once
+    Nes,
+}
+
+impl RandomizerKind
+{
+    pub fn build(&self, seed: u64) -> Box<dyn Randomizer>
+    {
+        match *self
+        {
+            RandomizerKind::SevenBag => Box::new(SevenBag::new(seed)),
+            RandomizerKind::PureRandom => Box::new(PureRandom::new(seed)),
+            RandomizerKind::History => Box::new(History::new(seed, TGM_ROLLS)),
+            RandomizerKind::Nes => Box::new(NesRandomizer::new(seed)),
+        }
+    }
+}
+
+pub struct SevenBag
+{
+    rng: Rng,
+    bag: Vec<Shape>,
+}
+
+impl SevenBag
+{
+    pub fn new(seed: u64) -> SevenBag
+    {
+        SevenBag
+        {
+            rng: Rng::new(seed),
+            bag: Vec::with_capacity(SHAPES.len()),
+        }
+    }
+
+    fn refill(&mut self)
+    {
+        self.bag.extend(SHAPES.iter().cloned());
+
+        // Fisher-Yates
+        for i in (1..self.bag.len()).rev()
+        {
+            let j = self.rng.below(i as u32 + 1) as usize;
+            self.bag.swap(i, j);
+        }
+    }
+}
+
+impl Randomizer for SevenBag
+{
+    fn next(&mut self) -> Shape
+    {
+        if self.bag.is_empty()
+        {
+            self.refill();
+        }
+
+        self.bag.pop().expect("bag was just refilled")
+    }
+}
+
+pub struct PureRandom
+{
+    rng: Rng,
+}
+
+impl PureRandom
+{
+    pub fn new(seed: u64) -> PureRandom
+    {
+        PureRandom
+        {
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Randomizer for PureRandom
+{
+    fn next(&mut self) -> Shape
+    {
+        SHAPES[self.rng.below(SHAPES.len() as u32) as usize]
+    }
+}
+
+/// Number of rolls the original TGM makes before giving up on avoiding the
+/// history
+pub const TGM_ROLLS: u32 = 4;
+
+pub struct History
+{
+    rng: Rng,
+    rolls: u32,
+    history: VecDeque<Shape>,
+    first: bool,
+}
+
+impl History
+{
+    pub fn new(seed: u64, rolls: u32) -> History
+    {
+        let mut history = VecDeque::with_capacity(4);
+
+        for _ in 0..4
+        {
+            history.push_back(Shape::ZBlock);
+        }
+
+        History
+        {
+            rng: Rng::new(seed),
+            rolls: rolls,
+            history: history,
+            first: true,
+        }
+    }
+
+    fn roll(&mut self) -> Shape
+    {
+        // The first piece is never an S, Z or O
+        if self.first
+        {
+            self.first = false;
+
+            let openers = [Shape::IBlock, Shape::JBlock, Shape::LBlock, Shape::TBlock];
+            return openers[self.rng.below(openers.len() as u32) as usize];
+        }
+
+        let mut shape = SHAPES[self.rng.below(SHAPES.len() as u32) as usize];
+
+        for _ in 1..self.rolls
+        {
+            if !self.history.iter().any(|x| *x == shape)
+            {
+                break;
+            }
+
+            shape = SHAPES[self.rng.below(SHAPES.len() as u32) as usize];
+        }
+
+        shape
+    }
+}
+
+impl Randomizer for History
+{
+    fn next(&mut self) -> Shape
+    {
+        let shape = self.roll();
+
+        self.history.pop_front();
+        self.history.push_back(shape);
+
+        shape
+    }
+}
+
+pub struct NesRandomizer
+{
+    rng: Rng,
+    previous: Option<Shape>,
+}
+
+impl NesRandomizer
+{
+    pub fn new(seed: u64) -> NesRandomizer
+    {
+        NesRandomizer
+        {
+            rng: Rng::new(seed),
+            previous: None,
+        }
+    }
+}
+
+impl Randomizer for NesRandomizer
+{
+    fn next(&mut self) -> Shape
+    {
+        // The NES rolls eight outcomes; the eighth, or a repeat of the last
+        // piece, triggers a single reroll over the seven pieces
+        let roll = self.rng.below(SHAPES.len() as u32 + 1) as usize;
+
+        let shape = if roll == SHAPES.len() || Some(SHAPES[roll]) == self.previous
+        {
+            SHAPES[self.rng.below(SHAPES.len() as u32) as usize]
+        }
+        else
+        {
+            SHAPES[roll]
+        };
+
+        self.previous = Some(shape);
+        shape
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use tetromino::SHAPES;
+    use super::RandomizerKind;
+
+    const KINDS: [RandomizerKind; 4] = [
+        RandomizerKind::SevenBag,
+        RandomizerKind::PureRandom,
+        RandomizerKind::History,
+        RandomizerKind::Nes,
+    ];
+
+    #[test]
+    fn same_seed_same_sequence()
+    {
+        for kind in KINDS.iter()
+        {
+            let mut a = kind.build(1234);
+            let mut b = kind.build(1234);
+
+            for _ in 0..200
+            {
+                assert_eq!(a.next(), b.next());
+            }
+        }
+    }
+
+    #[test]
+    fn bag_deals_every_shape_once()
+    {
+        let mut bag = RandomizerKind::SevenBag.build(99);
+
+        for _ in 0..10
+        {
+            let mut dealt: Vec<_> = (0..SHAPES.len()).map(|_| bag.next()).collect();
+
+            for shape in SHAPES.iter()
+            {
+                let index = dealt.iter().position(|x| x == shape)
+                    .expect("shape missing from bag");
+                dealt.remove(index);
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::io;
+
+use cgmath::Vector2;
+
+use glium;
+
+use cellmatrix::{Cell, CellMatrix};
+use font::{Align, Font, LINE_HEIGHT};
+use rect::Rect;
+use replay::Playback;
+use rules::{GameMode, SPRINT_LINES, ULTRA_TICKS};
+use scene::format_time;
+use sprite::{DrawContext, Sprite};
+use spritemanager::Textures;
+use tetris::Tetris;
+use tetromino::{Shape, Tetromino, SHAPES};
+
+
+/// The size the layout is drawn at. The window stretches it to fit.
+pub const SCREEN_WIDTH: u32 = 496;
+pub const SCREEN_HEIGHT: u32 = 376;
+
+const BOARD_POS: Vector2<f32> = Vector2 { x: 132.5, y: 8.5 };
+const CELL_SIZE: f32 = 16.0;
+const PREVIEW_POS: Vector2<f32> = Vector2 { x: 387.5, y: 86.5 };
+const HOLD_POS: Vector2<f32> = Vector2 { x: 27.5, y: 86.5 };
+const GHOST_ALPHA: f32 = 0.3;
+
+/// Vertical space given to each piece in the preview
+const PREVIEW_SPACING: f32 = 48.0;
+/// Pieces the preview box holds at full size. Longer previews are shrunk.
+const PREVIEW_SLOTS: usize = 3;
+
+/// Where the score, level, lines and time are listed, under the hold box
+const STATS_POS: Vector2<f32> = Vector2 { x: 12.0, y: 164.0 };
+/// Values are right aligned to the edge of the hold box
+const STATS_WIDTH: f32 = 80.0;
+/// Where the replay position is shown, under the preview
+const PLAYBACK_POS: Vector2<f32> = Vector2 { x: 336.0, y: 264.0 };
+/// Horizontal center of menus and the line their heading sits on
+const MENU_X: f32 = 212.5;
+const MENU_Y: f32 = 40.0;
+
+const TEXT_COLOR: [f32; 4] = [0.2, 0.25, 0.25, 1.0];
+const LABEL_COLOR: [f32; 4] = [0.45, 0.6, 0.55, 1.0];
+const SELECTED_COLOR: [f32; 4] = [0.85, 0.35, 0.1, 1.0];
+
+/// Draws a `Tetris` game. Nothing is cached between frames: every block is
+/// drawn by moving one sprite per color around.
+pub struct Renderer
+{
+    background: Sprite,
+
+    /// Block sprites, indexed by `Shape`
+    blocks: Vec<Sprite>,
+    ghosts: Vec<Sprite>,
+
+    font: Font,
+}
+
+impl Renderer
+{
+    pub fn new(display: &glium::backend::glutin_backend::GlutinFacade) -> io::Result<Renderer>
+    {
+        let background = try!(Sprite::new(
+            display,
+            Textures::Background,
+            Rect::new(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+            Vector2::new(0.0, 0.0)
+        ));
+
+        let mut blocks = vec![];
+        let mut ghosts = vec![];
+
+        for shape in SHAPES.iter()
+        {
+            let tint = shape.color();
+            blocks.push(try!(Renderer::block_sprite(display, tint)));
+
+            let mut ghost_tint = tint;
+            ghost_tint[3] = GHOST_ALPHA;
+            ghosts.push(try!(Renderer::block_sprite(display, ghost_tint)));
+        }
+
+        Ok(Renderer
+        {
+            background: background,
+
+            blocks: blocks,
+            ghosts: ghosts,
+
+            font: try!(Font::new(display)),
+        })
+    }
+
+    fn block_sprite(display: &glium::backend::glutin_backend::GlutinFacade, tint: [f32; 4])
+        -> io::Result<Sprite>
+    {
+        Sprite::new_tinted(
+            display,
+            Textures::SpriteSheet,
+            Rect::new(-8.0, -8.0, 16.0, 16.0),
+            Vector2::new(0.0, 0.0),
+            tint
+        )
+    }
+
+    pub fn draw_background(&mut self, context: &mut DrawContext)
+    {
+        context.draw(&self.background);
+    }
+
+    /// Draws the board, pieces, preview and hold over the background
+    pub fn draw(&mut self, context: &mut DrawContext, tetris: &Tetris)
+    {
+        self.draw_matrix(context, tetris.board(), BOARD_POS, false, 1.0);
+
+        match (tetris.current(), tetris.ghost_position())
+        {
+            (Some(piece), Some(ghost)) =>
+            {
+                self.draw_matrix(context, &piece.matrix, Renderer::cell_to_screen(ghost), true,
+                    1.0);
+
+                self.draw_matrix(context, &piece.matrix,
+                    Renderer::cell_to_screen(piece.cell_position), false, 1.0);
+            },
+
+            _ => ()
+        }
+
+        let preview = tetris.preview();
+
+        let scale = if preview.len() > PREVIEW_SLOTS
+        {
+            PREVIEW_SLOTS as f32 / preview.len() as f32
+        }
+        else
+        {
+            1.0
+        };
+
+        for (i, &shape) in preview.iter().enumerate()
+        {
+            let position = PREVIEW_POS + Vector2::new(0.0, i as f32 * PREVIEW_SPACING * scale);
+            self.draw_boxed(context, tetris, shape, position, scale);
+        }
+
+        match tetris.held()
+        {
+            Some(shape) => self.draw_boxed(context, tetris, shape, HOLD_POS, 1.0),
+            None => ()
+        }
+
+        self.draw_stats(context, tetris);
+    }
+
+    /// Draws one line of text with its top left corner at `position`
+    pub fn draw_text(&mut self, context: &mut DrawContext, position: Vector2<f32>, text: &str,
+        color: [f32; 4])
+    {
+        self.font.draw_text(context, position, text, color);
+    }
+
+    /// Draws a menu centered over the board: a heading, then one line per
+    /// entry with the selected one highlighted
+    pub fn draw_menu(&mut self, context: &mut DrawContext, heading: &str, lines: &[String],
+        selected: Option<usize>)
+    {
+        self.font.draw_text_aligned(context, Vector2::new(MENU_X, MENU_Y), heading, TEXT_COLOR,
+            2.0, Align::Center);
+
+        self.draw_entries(context, 0, lines, selected);
+    }
+
+    /// Draws menu lines under the heading, starting `row` lines down
+    pub fn draw_entries(&mut self, context: &mut DrawContext, row: usize, lines: &[String],
+        selected: Option<usize>)
+    {
+        for (i, line) in lines.iter().enumerate()
+        {
+            let y = MENU_Y + ((row + i) as f32 + 3.0) * LINE_HEIGHT * 1.5;
+            let position = Vector2::new(MENU_X, y);
+
+            let (text, color) = if selected == Some(i)
+            {
+                (format!("> {} <", line), SELECTED_COLOR)
+            }
+            else
+            {
+                (line.clone(), TEXT_COLOR)
+            };
+
+            self.font.draw_text_aligned(context, position, &text, color, 1.0, Align::Center);
+        }
+    }
+
+    /// Shows how far into a replay playback is and how fast it's going
+    pub fn draw_playback(&mut self, context: &mut DrawContext, playback: &Playback)
+    {
+        let speed = if playback.is_paused()
+        {
+            format!("paused")
+        }
+        else
+        {
+            format!("x{}", playback.speed())
+        };
+
+        let lines = [
+            format!("REPLAY {}", speed),
+            format!("{} / {}", format_time(playback.position() as u32),
+                format_time(playback.replay().frames.len() as u32)),
+        ];
+
+        for (i, line) in lines.iter().enumerate()
+        {
+            let position = PLAYBACK_POS + Vector2::new(0.0, i as f32 * LINE_HEIGHT);
+            self.font.draw_text(context, position, line, TEXT_COLOR);
+        }
+    }
+
+    /// Lists the score, level, lines and time. Sprint counts lines toward
+    /// the goal and Ultra counts the time down.
+    fn draw_stats(&mut self, context: &mut DrawContext, tetris: &Tetris)
+    {
+        let score = tetris.score();
+
+        let lines = match tetris.rules().mode
+        {
+            GameMode::Sprint => format!("{}/{}", score.lines, SPRINT_LINES),
+            _ => format!("{}", score.lines)
+        };
+
+        let time = match tetris.rules().mode
+        {
+            GameMode::Ultra => format_time(ULTRA_TICKS.saturating_sub(tetris.ticks())),
+            _ => format_time(tetris.ticks())
+        };
+
+        let stats = [
+            ("SCORE", format!("{}", score.score)),
+            ("LEVEL", format!("{}", score.level)),
+            ("LINES", lines),
+            ("TIME", time),
+        ];
+
+        for (i, &(label, ref value)) in stats.iter().enumerate()
+        {
+            let position = STATS_POS + Vector2::new(0.0, i as f32 * LINE_HEIGHT * 3.0);
+
+            self.font.draw_text(context, position, label, LABEL_COLOR);
+
+            self.font.draw_text_aligned(context, position + Vector2::new(STATS_WIDTH, LINE_HEIGHT),
+                value, TEXT_COLOR, 2.0, Align::Right);
+        }
+    }
+
+    /// Draws a piece in its spawn orientation, centered in a four cell wide
+    /// box whose top left cell is at `position`. A smaller `scale` shrinks
+    /// the piece toward the middle of the box's top row.
+    fn draw_boxed(&mut self, context: &mut DrawContext, tetris: &Tetris, shape: Shape,
+        position: Vector2<f32>, scale: f32)
+    {
+        let orientation = tetris.rotation_system().spawn_orientation(shape);
+        let tetromino = Tetromino::new(shape, Vector2::new(0, 0), orientation);
+
+        let cell_size = CELL_SIZE * scale;
+        let shrink = CELL_SIZE - cell_size;
+
+        // Center pieces narrower than the I piece
+        let inset = (4 - tetromino.matrix.width) as f32 * cell_size / 2.0;
+
+        let origin = position + Vector2::new(shrink * 1.5 + inset, -shrink / 2.0);
+        self.draw_matrix(context, &tetromino.matrix, origin, false, scale);
+    }
+
+    /// Draws the occupied cells of `matrix` with its top left cell at
+    /// `origin`, `scale` times the normal size
+    fn draw_matrix(&mut self, context: &mut DrawContext, matrix: &CellMatrix,
+        origin: Vector2<f32>, ghost: bool, scale: f32)
+    {
+        let cell_size = CELL_SIZE * scale;
+
+        for y in 0..matrix.height
+        {
+            for x in 0..matrix.width
+            {
+                match matrix.get_cell(x, y)
+                {
+                    Cell::Occupied(shape) =>
+                    {
+                        let sprite = if ghost
+                        {
+                            &mut self.ghosts[shape as usize]
+                        }
+                        else
+                        {
+                            &mut self.blocks[shape as usize]
+                        };
+
+                        sprite.set_position(origin +
+                            Vector2::new(x as f32 * cell_size, y as f32 * cell_size));
+                        sprite.set_scale(scale);
+                        context.draw(sprite);
+                    },
+
+                    _ => ()
+                }
+            }
+        }
+    }
+
+    fn cell_to_screen(position: Vector2<i8>) -> Vector2<f32>
+    {
+        BOARD_POS + Vector2::new(position.x as f32 * CELL_SIZE, position.y as f32 * CELL_SIZE)
+    }
+}
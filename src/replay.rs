@@ -0,0 +1,710 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use input::InputFrame;
+use randomizer::RandomizerKind;
+use rotation::RotationKind;
+use rules::{GameMode, LockReset, Rules};
+use scoring::{Score, ScoringKind};
+use tetris::{Tetris, TICKS_PER_SECOND};
+
+const MAGIC: &'static [u8] = b"RTRP";
+/// Version 2 added checkpoints and version 3 the game mode
+const VERSION: u8 = 3;
+
+/// Longest replay that will be loaded, a day of play. The run lengths in a
+/// file could otherwise ask for any amount of memory.
+const MAX_FRAMES: usize = 24 * 60 * 60 * TICKS_PER_SECOND as usize;
+/// Bytes per run of input frames: the frame and a u16 count
+const RUN_SIZE: usize = 3;
+
+/// Frames between recorded checkpoints
+pub const CHECKPOINT_INTERVAL: u32 = 600;
+
+/// Playback speeds, slowest first
+pub const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 2;
+
+// Enums are stored as their index in these lists, so only ever append
+const ROTATIONS: [RotationKind; 3] = [RotationKind::Srs, RotationKind::Ars, RotationKind::Nes];
+const RANDOMIZERS: [RandomizerKind; 4] = [
+    RandomizerKind::SevenBag,
+    RandomizerKind::PureRandom,
+    RandomizerKind::History,
+    RandomizerKind::Nes,
+];
+const LOCK_RESETS: [LockReset; 3] = [LockReset::Extended, LockReset::Infinite, LockReset::Step];
+const SCORINGS: [ScoringKind; 2] = [ScoringKind::Guideline, ScoringKind::Nes];
+const MODES: [GameMode; 3] = [GameMode::Marathon, GameMode::Sprint, GameMode::Ultra];
+
+/// The state of a game after a given number of frames, used to check that a
+/// replay still plays out the way it was recorded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Checkpoint
+{
+    pub frame: u32,
+    pub score: u32,
+    pub lines: u32,
+    pub board_hash: u64,
+}
+
+impl Checkpoint
+{
+    pub fn new(frame: u32, tetris: &Tetris) -> Checkpoint
+    {
+        Checkpoint
+        {
+            frame: frame,
+            score: tetris.score().score,
+            lines: tetris.score().lines,
+            board_hash: tetris.board().hash(),
+        }
+    }
+}
+
+/// Everything needed to play a game back: the rules, the seed and the
+/// buttons held on every tick.
+///
+/// On disk a replay is the magic bytes `RTRP`, a version byte, the seed, the
+/// rules, the frames run-length encoded as (buttons, count) pairs and then
+/// the checkpoints. Numbers are little endian.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay
+{
+    pub rules: Rules,
+    pub seed: u64,
+    pub frames: Vec<InputFrame>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Replay
+{
+    pub fn new(rules: Rules, seed: u64) -> Replay
+    {
+        Replay
+        {
+            rules: rules,
+            seed: seed,
+            frames: vec![],
+            checkpoints: vec![],
+        }
+    }
+
+    pub fn push(&mut self, frame: InputFrame)
+    {
+        self.frames.push(frame);
+    }
+
+    /// Adds a frame that was just stepped into `tetris`, checkpointing every
+    /// `CHECKPOINT_INTERVAL` frames
+    pub fn record(&mut self, frame: InputFrame, tetris: &Tetris)
+    {
+        self.push(frame);
+
+        let count = self.frames.len() as u32;
+
+        if count % CHECKPOINT_INTERVAL == 0
+        {
+            self.checkpoints.push(Checkpoint::new(count, tetris));
+        }
+    }
+
+    /// Checkpoints the final state of the game
+    pub fn finish(&mut self, tetris: &Tetris)
+    {
+        let count = self.frames.len() as u32;
+
+        if self.checkpoints.last().map(|x| x.frame) != Some(count)
+        {
+            self.checkpoints.push(Checkpoint::new(count, tetris));
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay>
+    {
+        let mut file = try!(File::open(path));
+        Replay::read_from(&mut file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()>
+    {
+        let mut file = try!(File::create(path));
+        self.write_to(&mut file)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>
+    {
+        let mut out = vec![];
+        out.extend(MAGIC.iter().cloned());
+        out.push(VERSION);
+        push_u64(&mut out, self.seed);
+
+        let rules = &self.rules;
+        out.push(encode(&ROTATIONS, &rules.rotation));
+        out.push(encode(&RANDOMIZERS, &rules.randomizer));
+        out.push(rules.preview_count as u8);
+        out.push(rules.hold_enabled as u8);
+        push_u32(&mut out, rules.lock_delay);
+        out.push(encode(&LOCK_RESETS, &rules.lock_reset));
+        push_u32(&mut out, rules.das);
+        push_u32(&mut out, rules.arr);
+        push_u32(&mut out, rules.soft_drop_factor.to_bits());
+        out.push(encode(&SCORINGS, &rules.scoring));
+        out.push(rules.partial_lock_out as u8);
+        out.push(encode(&MODES, &rules.mode));
+
+        let runs = runs(&self.frames);
+        push_u32(&mut out, runs.len() as u32);
+
+        for &(frame, count) in runs.iter()
+        {
+            out.push(frame.bits());
+            push_u16(&mut out, count);
+        }
+
+        push_u32(&mut out, self.checkpoints.len() as u32);
+
+        for checkpoint in self.checkpoints.iter()
+        {
+            push_u32(&mut out, checkpoint.frame);
+            push_u32(&mut out, checkpoint.score);
+            push_u32(&mut out, checkpoint.lines);
+            push_u64(&mut out, checkpoint.board_hash);
+        }
+
+        writer.write_all(&out)
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Replay>
+    {
+        let mut data = vec![];
+        try!(reader.read_to_end(&mut data));
+
+        let mut bytes = Bytes { data: &data, pos: 0 };
+
+        if try!(bytes.take(MAGIC.len())) != MAGIC
+        {
+            return Err(invalid("not a replay file"));
+        }
+
+        let version = try!(bytes.u8());
+
+        if version == 0 || version > VERSION
+        {
+            return Err(invalid("unsupported replay version"));
+        }
+
+        let seed = try!(bytes.u64());
+
+        let mut rules = Rules
+        {
+            rotation: try!(decode(&ROTATIONS, try!(bytes.u8()))),
+            randomizer: try!(decode(&RANDOMIZERS, try!(bytes.u8()))),
+            preview_count: try!(bytes.u8()) as usize,
+            hold_enabled: try!(bytes.u8()) != 0,
+            lock_delay: try!(bytes.u32()),
+            lock_reset: try!(decode(&LOCK_RESETS, try!(bytes.u8()))),
+            das: try!(bytes.u32()),
+            arr: try!(bytes.u32()),
+            soft_drop_factor: f32::from_bits(try!(bytes.u32())),
+            scoring: try!(decode(&SCORINGS, try!(bytes.u8()))),
+            partial_lock_out: try!(bytes.u8()) != 0,
+            mode: GameMode::Marathon,
+        };
+
+        if version >= 3
+        {
+            rules.mode = try!(decode(&MODES, try!(bytes.u8())));
+        }
+
+        let run_count = try!(bytes.u32()) as usize;
+
+        if run_count > bytes.remaining() / RUN_SIZE
+        {
+            return Err(invalid("replay is truncated"));
+        }
+
+        let mut frames = vec![];
+
+        for _ in 0..run_count
+        {
+            let frame = InputFrame::from_bits(try!(bytes.u8()));
+            let count = try!(bytes.u16()) as usize;
+
+            if frames.len() + count > MAX_FRAMES
+            {
+                return Err(invalid("replay is too long"));
+            }
+
+            frames.extend((0..count).map(|_| frame));
+        }
+
+        let mut checkpoints = vec![];
+
+        if version >= 2
+        {
+            for _ in 0..try!(bytes.u32())
+            {
+                checkpoints.push(Checkpoint
+                {
+                    frame: try!(bytes.u32()),
+                    score: try!(bytes.u32()),
+                    lines: try!(bytes.u32()),
+                    board_hash: try!(bytes.u64()),
+                });
+            }
+        }
+
+        Ok(Replay
+        {
+            rules: rules,
+            seed: seed,
+            frames: frames,
+            checkpoints: checkpoints,
+        })
+    }
+
+    /// Runs the replay through the engine, comparing against every
+    /// checkpoint on the way
+    pub fn verify(&self) -> Verification
+    {
+        let mut tetris = Tetris::new(self.rules.clone(), self.seed);
+        let mut checkpoints = self.checkpoints.iter().peekable();
+        let mut divergence = None;
+        let mut checked = 0;
+
+        // Checkpoint i is taken after i frames, so 0 is before any input
+        for i in 0..self.frames.len() + 1
+        {
+            if i > 0
+            {
+                tetris.step(self.frames[i - 1]);
+            }
+
+            while checkpoints.peek().map(|x| x.frame as usize) == Some(i)
+            {
+                let expected = *checkpoints.next().unwrap();
+                checked += 1;
+
+                if divergence.is_none() && Checkpoint::new(i as u32, &tetris) != expected
+                {
+                    divergence = Some(expected);
+                }
+            }
+        }
+
+        // Checkpoints past the end of the input can never match
+        for expected in checkpoints
+        {
+            checked += 1;
+
+            if divergence.is_none()
+            {
+                divergence = Some(*expected);
+            }
+        }
+
+        Verification
+        {
+            score: *tetris.score(),
+            pieces: tetris.pieces(),
+            ticks: tetris.ticks(),
+            frames: self.frames.len() as u32,
+            board_hash: tetris.board().hash(),
+            checkpoints: checked,
+            divergence: divergence,
+        }
+    }
+}
+
+/// The outcome of `Replay::verify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification
+{
+    pub score: Score,
+    pub pieces: u32,
+    /// Ticks of game time, see `TICKS_PER_SECOND`
+    pub ticks: u32,
+    pub frames: u32,
+    pub board_hash: u64,
+
+    pub checkpoints: u32,
+    /// The first checkpoint that didn't match
+    pub divergence: Option<Checkpoint>,
+}
+
+/// Collapses repeated frames into (frame, count) pairs
+fn runs(frames: &[InputFrame]) -> Vec<(InputFrame, u16)>
+{
+    let mut runs: Vec<(InputFrame, u16)> = vec![];
+
+    for &frame in frames.iter()
+    {
+        let repeat = match runs.last()
+        {
+            Some(&(last, count)) => last == frame && count < u16::max_value(),
+            None => false
+        };
+
+        if repeat
+        {
+            runs.last_mut().unwrap().1 += 1;
+        }
+        else
+        {
+            runs.push((frame, 1));
+        }
+    }
+
+    runs
+}
+
+fn encode<T: PartialEq>(list: &[T], value: &T) -> u8
+{
+    list.iter().position(|x| x == value).expect("enum missing from replay table") as u8
+}
+
+fn decode<T: Copy>(list: &[T], id: u8) -> io::Result<T>
+{
+    match list.get(id as usize)
+    {
+        Some(x) => Ok(*x),
+        None => Err(invalid("unknown rule in replay"))
+    }
+}
+
+fn invalid(message: &'static str) -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16)
+{
+    out.push(value as u8);
+    out.push((value >> 8) as u8);
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32)
+{
+    push_u16(out, value as u16);
+    push_u16(out, (value >> 16) as u16);
+}
+
+fn push_u64(out: &mut Vec<u8>, value: u64)
+{
+    push_u32(out, value as u32);
+    push_u32(out, (value >> 32) as u32);
+}
+
+/// Reads little endian numbers off the front of a byte slice
+struct Bytes<'a>
+{
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bytes<'a>
+{
+    fn remaining(&self) -> usize
+    {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]>
+    {
+        if self.pos + count > self.data.len()
+        {
+            return Err(invalid("replay is truncated"));
+        }
+
+        let bytes = &self.data[self.pos..self.pos + count];
+        self.pos += count;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8>
+    {
+        Ok(try!(self.take(1))[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16>
+    {
+        let bytes = try!(self.take(2));
+        Ok(bytes[0] as u16 | (bytes[1] as u16) << 8)
+    }
+
+    fn u32(&mut self) -> io::Result<u32>
+    {
+        let low = try!(self.u16()) as u32;
+        let high = try!(self.u16()) as u32;
+        Ok(low | high << 16)
+    }
+
+    fn u64(&mut self) -> io::Result<u64>
+    {
+        let low = try!(self.u32()) as u64;
+        let high = try!(self.u32()) as u64;
+        Ok(low | high << 32)
+    }
+}
+
+/// Plays a replay back through the engine
+pub struct Playback
+{
+    replay: Replay,
+    tetris: Tetris,
+
+    /// The next frame to play
+    position: usize,
+    paused: bool,
+    /// Index into `SPEEDS`
+    speed: usize,
+    /// Fractions of a frame owed at the current speed
+    frame_acc: f32,
+}
+
+impl Playback
+{
+    pub fn new(replay: Replay) -> Playback
+    {
+        let tetris = Tetris::new(replay.rules.clone(), replay.seed);
+
+        Playback
+        {
+            replay: replay,
+            tetris: tetris,
+
+            position: 0,
+            paused: false,
+            speed: NORMAL_SPEED,
+            frame_acc: 0.0,
+        }
+    }
+
+    /// Called once per game tick
+    pub fn update(&mut self)
+    {
+        if self.paused
+        {
+            return;
+        }
+
+        self.frame_acc += SPEEDS[self.speed];
+
+        while self.frame_acc >= 1.0
+        {
+            self.frame_acc -= 1.0;
+
+            if !self.advance()
+            {
+                self.frame_acc = 0.0;
+                break;
+            }
+        }
+    }
+
+    /// Plays the next frame, returning false at the end of the replay
+    fn advance(&mut self) -> bool
+    {
+        match self.replay.frames.get(self.position)
+        {
+            Some(&frame) =>
+            {
+                self.tetris.step(frame);
+                self.position += 1;
+                true
+            },
+
+            None => false
+        }
+    }
+
+    pub fn toggle_pause(&mut self)
+    {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses and plays a single frame
+    pub fn step_frame(&mut self)
+    {
+        self.paused = true;
+        self.advance();
+    }
+
+    pub fn faster(&mut self)
+    {
+        if self.speed + 1 < SPEEDS.len()
+        {
+            self.speed += 1;
+        }
+    }
+
+    pub fn slower(&mut self)
+    {
+        if self.speed > 0
+        {
+            self.speed -= 1;
+        }
+    }
+
+    /// Jumps to a frame. Going backwards replays the game from the start.
+    pub fn seek(&mut self, frame: usize)
+    {
+        let target = frame.min(self.replay.frames.len());
+
+        if target < self.position
+        {
+            self.tetris = Tetris::new(self.replay.rules.clone(), self.replay.seed);
+            self.position = 0;
+        }
+
+        while self.position < target
+        {
+            self.advance();
+        }
+
+        self.frame_acc = 0.0;
+    }
+
+    pub fn tetris(&self) -> &Tetris
+    {
+        &self.tetris
+    }
+
+    pub fn replay(&self) -> &Replay
+    {
+        &self.replay
+    }
+
+    pub fn position(&self) -> usize
+    {
+        self.position
+    }
+
+    pub fn is_paused(&self) -> bool
+    {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f32
+    {
+        SPEEDS[self.speed]
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use input::{Button, InputFrame};
+    use rules::Rules;
+    use tetris::Tetris;
+    use super::{push_u16, push_u32, Playback, Replay, MAX_FRAMES};
+
+    fn sample() -> Replay
+    {
+        let mut replay = Replay::new(Rules::classic(), 42);
+
+        for i in 0..2000u32
+        {
+            let mut frame = InputFrame::empty();
+            frame.set(Button::Left, i % 90 < 20);
+            frame.set(Button::HardDrop, i % 45 == 0);
+            frame.set(Button::RotateRight, i % 30 == 7);
+            replay.push(frame);
+        }
+
+        replay
+    }
+
+    #[test]
+    fn round_trip()
+    {
+        let replay = sample();
+        let mut bytes = vec![];
+
+        replay.write_to(&mut bytes).unwrap();
+
+        assert_eq!(Replay::read_from(&mut &bytes[..]).unwrap(), replay);
+        assert!(Replay::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(Replay::read_from(&mut &b"junk"[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_runs()
+    {
+        // An empty replay ends with its run count and checkpoint count
+        let mut bytes = vec![];
+        Replay::new(Rules::classic(), 42).write_to(&mut bytes).unwrap();
+        let header = bytes.len() - 8;
+
+        let mut huge_count = bytes[..header].to_vec();
+        push_u32(&mut huge_count, !0);
+        push_u32(&mut huge_count, 0);
+        assert!(Replay::read_from(&mut &huge_count[..]).is_err());
+
+        let mut too_long = bytes[..header].to_vec();
+        let runs = MAX_FRAMES / 0xffff + 1;
+        push_u32(&mut too_long, runs as u32);
+
+        for _ in 0..runs
+        {
+            too_long.push(0);
+            push_u16(&mut too_long, 0xffff);
+        }
+
+        push_u32(&mut too_long, 0);
+        assert!(Replay::read_from(&mut &too_long[..]).is_err());
+    }
+
+    #[test]
+    fn recorded_games_verify()
+    {
+        let sample = sample();
+        let mut tetris = Tetris::new(sample.rules.clone(), sample.seed);
+        let mut replay = Replay::new(sample.rules.clone(), sample.seed);
+
+        for frame in sample.frames.iter()
+        {
+            tetris.step(*frame);
+            replay.record(*frame, &tetris);
+        }
+
+        replay.finish(&tetris);
+
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes).unwrap();
+        let replay = Replay::read_from(&mut &bytes[..]).unwrap();
+
+        let verification = replay.verify();
+        assert_eq!(verification.checkpoints, 4);
+        assert_eq!(verification.divergence, None);
+        assert_eq!(verification.board_hash, tetris.board().hash());
+
+        let mut tampered = replay.clone();
+        tampered.frames[100] = InputFrame::empty().with(Button::HardDrop);
+        assert!(tampered.verify().divergence.is_some());
+    }
+
+    #[test]
+    fn seeking_matches_playing()
+    {
+        let replay = sample();
+        let mut tetris = Tetris::new(replay.rules.clone(), replay.seed);
+
+        for frame in replay.frames[..1500].iter()
+        {
+            tetris.step(*frame);
+        }
+
+        let mut playback = Playback::new(replay);
+        playback.seek(1800);
+        playback.seek(1500);
+
+        assert_eq!(playback.position(), 1500);
+        assert_eq!(playback.tetris().board(), tetris.board());
+        assert_eq!(playback.tetris().score(), tetris.score());
+    }
+}
@@ -0,0 +1,58 @@
+/// A small xorshift* generator.
+///
+/// We don't use a library RNG because the piece sequence for a given seed
+/// has to stay the same across platforms and dependency upgrades.
+#[derive(Debug, Clone)]
+pub struct Rng
+{
+    state: u64,
+}
+
+impl Rng
+{
+    pub fn new(seed: u64) -> Rng
+    {
+        // Run the seed through splitmix64 so nearby seeds give unrelated
+        // sequences, and make sure the state is never zero
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z = z ^ (z >> 31);
+
+        Rng
+        {
+            state: if z == 0 { 0x9E3779B97F4A7C15 } else { z },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    pub fn next_u32(&mut self) -> u32
+    {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a uniformly distributed number in `0..bound`
+    pub fn below(&mut self, bound: u32) -> u32
+    {
+        // Reject the top of the range so every result is equally likely
+        let zone = ::std::u32::MAX - ::std::u32::MAX % bound;
+
+        loop
+        {
+            let x = self.next_u32();
+
+            if x < zone
+            {
+                return x % bound;
+            }
+        }
+    }
+}
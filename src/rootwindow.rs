@@ -1,5 +1,7 @@
+use std::fs;
 use std::thread;
 use std::io;
+use std::path::Path;
 
 use cgmath;
 
@@ -8,14 +10,62 @@ use clock_ticks;
 use glium;
 use glium::{DisplayBuild, Surface};
 use glium::glutin;
-
+use glium::glutin::{ElementState, VirtualKeyCode};
+
+use bindings::{Action, Bindings, ControlsMenu};
+use config::Config;
+use gamepad;
+use gamepad::{Gamepad, GamepadEvent};
+use input::{InputFrame, InputMask};
+use renderer::{Renderer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use replay::{Playback, Replay};
+use rules::Rules;
+use scene::{MenuInput, Scene, Transition};
+use sprite::DrawContext;
 use spritemanager::SpriteManager;
-use tetris::Tetris;
+use tetris::{GameState, Tetris, TICKS_PER_SECOND};
+
+/// Where finished games are saved
+const REPLAY_DIR: &'static str = "replays";
+
+/// Where the key bindings are kept
+const CONTROLS_FILE: &'static str = "controls.cfg";
+
+/// Opens the controls menu. Not rebindable, so it can always be reached.
+const CONTROLS_KEY: VirtualKeyCode = VirtualKeyCode::F2;
+
+/// The joystick device read on Linux
+#[cfg(target_os = "linux")]
+const JOYSTICK_DEVICE: &'static str = "/dev/input/js0";
+
+/// How far the arrow keys seek during playback
+const SEEK_SECONDS: usize = 5;
 
 /// The window
 pub struct RootWindow
 {
     tetris: Tetris,
+    renderer: Renderer,
+
+    /// The game being played, saved when it ends
+    recording: Replay,
+    /// When set, the replay is shown instead of the game
+    playback: Option<Playback>,
+
+    /// The screens shown, the top one getting input
+    scenes: Vec<Scene>,
+    /// Rules for new games, changed from the options menu
+    rules: Rules,
+
+    bindings: Bindings,
+
+    keys_down: Vec<VirtualKeyCode>,
+    gamepad: Option<Gamepad>,
+    /// Buttons pressed since the last tick, so taps shorter than a tick
+    /// still register
+    pressed: InputFrame,
+    /// Buttons held on entering the game, ignored until released
+    mask: InputMask,
 
     pub display: glium::backend::glutin_backend::GlutinFacade,
     sprite_manager: Option<SpriteManager>,
@@ -35,65 +85,106 @@ pub struct Vertex
     pub tex_coords: [f32; 2],
 }
 
-pub enum GameState
-{
-    Exit,
-    Play,
-}
-
 impl RootWindow
 {
-    /// Creates a new root window
-    pub fn new(width: u32, height: u32) -> io::Result<RootWindow>
+    /// Creates a new root window showing the title screen. `rules` and `seed`
+    /// are for the first game if the title is skipped; the rest comes from
+    /// `config`.
+    pub fn new(config: &Config, rules: Rules, seed: u64) -> io::Result<RootWindow>
     {
-        let display = glium::glutin::WindowBuilder::new()
-            .with_dimensions(width, height)
-            .with_title(format!("Rustris"))
+        let mut builder = glium::glutin::WindowBuilder::new()
+            .with_dimensions(config.width, config.height)
+            .with_title(format!("Rustris"));
+
+        if config.fullscreen
+        {
+            builder = builder.with_fullscreen(glutin::get_primary_monitor());
+        }
+
+        let display = try!(builder
             .build_glium()
-            .unwrap();
+            .map_err(|e| io::Error::new(io::ErrorKind::Other,
+                format!("couldn't open a window: {:?}", e))));
 
-        let program = program!(&display,
+        let program = try!(program!(&display,
             140 =>
             {
                 vertex: include_str!("shaders/140.vert"),
                 fragment: include_str!("shaders/140.frag"),
             },
-        ).unwrap();
+        ).map_err(|e| io::Error::new(io::ErrorKind::Other,
+            format!("couldn't compile the shaders: {:?}", e))));
 
         implement_vertex!(Vertex, position, color, tex_coords);
 
+        let renderer = try!(Renderer::new(&display));
+
         Ok(RootWindow
         {
-            tetris: Tetris::new(width, height),
+            recording: Replay::new(rules.clone(), seed),
+            playback: None,
+
+            tetris: Tetris::new(rules, seed),
+            renderer: renderer,
+
+            scenes: vec![Scene::title()],
+            rules: config.rules.clone(),
+
+            bindings: RootWindow::load_bindings(),
+
+            keys_down: vec![],
+            gamepad: RootWindow::open_gamepad(),
+            pressed: InputFrame::empty(),
+            mask: InputMask::new(),
 
             display: display,
             sprite_manager: None,
 
             program: program,
-            ortho_matrix: cgmath::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0),
+            ortho_matrix: cgmath::ortho(0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32, 0.0,
+                -1.0, 1.0),
 
-            max_frame_rate: 60,
+            max_frame_rate: config.max_frame_rate,
             delta_time: 0.0,
         })
     }
 
+    /// Shows a replay instead of taking input for a game
+    pub fn play(&mut self, replay: Replay)
+    {
+        self.playback = Some(Playback::new(replay));
+        self.scenes = vec![Scene::InGame];
+    }
+
+    /// Goes straight into the first game
+    pub fn skip_title(&mut self)
+    {
+        self.scenes = vec![Scene::title(), Scene::InGame];
+    }
+
     /// Starts the draw loop
     pub fn start(&mut self)
     {
         self.sprite_manager = Some(SpriteManager::new(self));
-        
-        self.tetris.start(&self.display);
 
         let mut accumulator = 0;
         let mut previous_clock = clock_ticks::precise_time_ns();
 
+        // The time between draws in nanoseconds
+        let frame_time = (1.0 / self.max_frame_rate as f64 * 1E+9) as u64;
+        let mut next_draw = previous_clock;
+
         loop
         {
             // Get input
             match self.do_input()
             {
-                GameState::Exit => return,
-                GameState::Play => ()
+                GameState::Exit =>
+                {
+                    self.save_recording();
+                    return;
+                },
+                GameState::Play | GameState::GameOver => ()
             }
 
         
@@ -102,8 +193,8 @@ impl RootWindow
             // Add the time between the last loop
             accumulator += now - previous_clock;
 
-            // The time for each frame in nanoseconds
-            let fixed_time_stamp = (1.0 / self.max_frame_rate as f64 * 1E+9) as u64;
+            // The time for each tick in nanoseconds
+            let fixed_time_stamp = (1.0 / TICKS_PER_SECOND as f64 * 1E+9) as u64;
 
             // Loop the amount of update ticks that are saved up
             while accumulator >= fixed_time_stamp
@@ -116,15 +207,35 @@ impl RootWindow
 
 
                 // Update the game logic
-                self.tetris.update();
+                let held = self.held_inputs();
+                let inputs = self.mask.apply(held).union(self.pressed);
+
+                self.pressed = InputFrame::empty();
+
+                match self.playback
+                {
+                    Some(ref mut x) => x.update(),
+                    None =>
+                    {
+                        self.update_scene();
+                        self.step_game(inputs);
+                    }
+                }
             }
             
-            // Finally, draw the sprites
-            self.draw();
+            // Finally, draw the sprites, at most `max_frame_rate` times a second
+            if now >= next_draw
+            {
+                self.draw();
+                next_draw = now + frame_time;
+            }
 
             previous_clock = now;
 
-            thread::sleep_ms(((fixed_time_stamp - accumulator) as f64 / 1E+6) as u32);
+            // Sleep until the next tick or the next draw, whichever is sooner
+            let wait = (fixed_time_stamp - accumulator).min(next_draw - now);
+
+            thread::sleep_ms((wait as f64 / 1E+6) as u32);
         }
     }
 
@@ -141,7 +252,38 @@ impl RootWindow
             None => panic!("Missing sprite manager!")
         };
 
-        self.tetris.draw_sprites(&mut target, &self.program, sprite_manager, &self.ortho_matrix);
+        {
+            let mut context = DrawContext
+            {
+                target: &mut target,
+                program: &self.program,
+                sprite_manager: sprite_manager,
+                projection: &self.ortho_matrix,
+            };
+
+            self.renderer.draw_background(&mut context);
+
+            let tetris = match self.playback
+            {
+                Some(ref x) => x.tetris(),
+                None => &self.tetris
+            };
+
+            let scene = self.scene();
+
+            if scene.shows_board()
+            {
+                self.renderer.draw(&mut context, tetris);
+            }
+
+            scene.draw(&mut self.renderer, &mut context, &self.rules, tetris, &self.bindings);
+
+            match self.playback
+            {
+                Some(ref x) => self.renderer.draw_playback(&mut context, x),
+                None => ()
+            }
+        }
 
         target.finish();
     }
@@ -156,7 +298,11 @@ impl RootWindow
             state = match event
             {
                 glutin::Event::Closed => GameState::Exit,
-                _ => self.tetris.handle_input(event),
+
+                glutin::Event::KeyboardInput(key_state, _, keycode) =>
+                    self.handle_keyboard(key_state, keycode),
+
+                _ => self.tetris.state(),
             };
 
             match state
@@ -166,6 +312,452 @@ impl RootWindow
             }
         }
 
+        let events = match self.gamepad
+        {
+            Some(ref mut x) => x.poll(),
+            None => vec![]
+        };
+
+        for event in events
+        {
+            match self.handle_gamepad(event)
+            {
+                GameState::Exit => return GameState::Exit,
+                _ => ()
+            }
+        }
+
         state
     }
+
+    fn scene(&self) -> Scene
+    {
+        match self.scenes.last()
+        {
+            Some(x) => *x,
+            None => panic!("Empty scene stack!")
+        }
+    }
+
+    /// Advances the timers of the top scene
+    fn update_scene(&mut self)
+    {
+        let transition = match self.scenes.last_mut()
+        {
+            Some(x) => x.update(),
+            None => Transition::Stay
+        };
+
+        if transition != Transition::Stay
+        {
+            self.apply(transition);
+        }
+    }
+
+    /// Passes a menu command to the top scene
+    fn menu_input(&mut self, input: MenuInput) -> GameState
+    {
+        let transition = match self.scenes.last_mut()
+        {
+            Some(x) => x.handle(input, &mut self.rules),
+            None => Transition::Stay
+        };
+
+        self.apply(transition)
+    }
+
+    fn apply(&mut self, transition: Transition) -> GameState
+    {
+        let enters_game = match transition
+        {
+            Transition::Pop | Transition::StartGame(_) | Transition::Restart => true,
+            _ => false
+        };
+
+        match transition
+        {
+            Transition::Stay => (),
+            Transition::Push(x) => self.scenes.push(x),
+            Transition::Pop => { self.scenes.pop(); },
+
+            Transition::Replace(x) =>
+            {
+                self.scenes.pop();
+                self.scenes.push(x);
+            },
+
+            Transition::StartGame(mode) =>
+            {
+                let mut rules = self.rules.clone();
+                rules.mode = mode;
+
+                self.new_game(rules);
+            },
+
+            Transition::Restart =>
+            {
+                let rules = self.tetris.rules().clone();
+                self.new_game(rules);
+            },
+
+            Transition::ToTitle =>
+            {
+                self.save_recording();
+                self.scenes = vec![Scene::title()];
+            },
+
+            Transition::Quit => return GameState::Exit,
+        }
+
+        // Whatever was held to get here, like the key that confirmed a menu,
+        // mustn't also move the piece
+        if enters_game && self.scene().runs_game()
+        {
+            let held = self.held_inputs();
+            self.mask.mask(held);
+        }
+
+        GameState::Play
+    }
+
+    /// The buttons held on the keyboard and gamepad
+    fn held_inputs(&self) -> InputFrame
+    {
+        let inputs = self.bindings.frame(&self.keys_down);
+
+        match self.gamepad
+        {
+            Some(ref x) => inputs.union(x.frame()),
+            None => inputs
+        }
+    }
+
+    /// Gamepad events go through the same bindings as keys
+    fn handle_gamepad(&mut self, event: GamepadEvent) -> GameState
+    {
+        match (event, self.scenes.last_mut())
+        {
+            // A menu waiting for a binding takes the input as the binding;
+            // otherwise the pad moves through the menu like in any other
+            (GamepadEvent::Button { button, pressed: true },
+                Some(&mut Scene::Controls(ref mut menu))) if menu.is_waiting() =>
+            {
+                menu.handle_pad_button(button, &mut self.bindings);
+                return GameState::Play;
+            },
+
+            (GamepadEvent::Axis { axis, value }, Some(&mut Scene::Controls(ref mut menu)))
+                if menu.is_waiting() =>
+            {
+                match gamepad::pushed_end(axis, value, self.bindings.deadzone)
+                {
+                    Some(x) =>
+                    {
+                        menu.handle_pad_axis(x, &mut self.bindings);
+                        return GameState::Play;
+                    },
+
+                    None => ()
+                }
+            },
+
+            _ => ()
+        }
+
+        let changes = match self.gamepad
+        {
+            Some(ref mut x) => x.handle(event, &self.bindings),
+            None => return GameState::Play
+        };
+
+        if self.playback.is_some()
+        {
+            return GameState::Play;
+        }
+
+        for (action, _) in changes.into_iter().filter(|x| x.1)
+        {
+            let state = match self.scene()
+            {
+                Scene::InGame => self.press_action(action),
+                Scene::Controls(_) =>
+                {
+                    match MenuInput::from_action(action)
+                    {
+                        Some(x) => self.handle_controls_input(x),
+                        None => ()
+                    }
+
+                    GameState::Play
+                },
+
+                _ => match MenuInput::from_action(action)
+                {
+                    Some(x) => self.menu_input(x),
+                    None => GameState::Play
+                }
+            };
+
+            if state == GameState::Exit
+            {
+                return state;
+            }
+        }
+
+        GameState::Play
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_gamepad() -> Option<Gamepad>
+    {
+        use gamepad::LinuxJoystick;
+
+        LinuxJoystick::open(JOYSTICK_DEVICE).ok().map(|x| Gamepad::new(Box::new(x)))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_gamepad() -> Option<Gamepad>
+    {
+        None
+    }
+
+    /// Advances the game by a tick, recording the inputs. Nothing moves
+    /// unless the game is the top scene, so pausing freezes every timer.
+    fn step_game(&mut self, inputs: InputFrame)
+    {
+        if !self.scene().runs_game() || self.tetris.state() != GameState::Play
+        {
+            return;
+        }
+
+        let state = self.tetris.step(inputs);
+        self.recording.record(inputs, &self.tetris);
+
+        if state == GameState::GameOver
+        {
+            self.save_recording();
+            self.apply(Transition::Push(Scene::GameOver { ticks: 0 }));
+        }
+    }
+
+    /// Saves the game so far and starts another
+    fn new_game(&mut self, rules: Rules)
+    {
+        self.save_recording();
+
+        self.tetris = Tetris::new(rules, clock_ticks::precise_time_ns());
+        self.recording = Replay::new(self.tetris.rules().clone(), self.tetris.seed());
+        self.pressed = InputFrame::empty();
+
+        self.scenes = vec![Scene::title(), Scene::InGame];
+    }
+
+    /// Writes the game played so far to the replay directory
+    fn save_recording(&mut self)
+    {
+        if self.recording.frames.is_empty()
+        {
+            return;
+        }
+
+        self.recording.finish(&self.tetris);
+
+        let path = RootWindow::replay_path(self.recording.seed);
+
+        let result = fs::create_dir_all(REPLAY_DIR)
+            .and_then(|_| self.recording.save(&path));
+
+        match result
+        {
+            Ok(_) => self.recording = Replay::new(self.tetris.rules().clone(), self.tetris.seed()),
+            Err(e) => eprintln!("Couldn't save replay to {}: {}", path, e)
+        }
+    }
+
+    /// A path in the replay directory named after the seed that no replay is
+    /// saved at yet. Seeds come from the clock so they rarely repeat, but an
+    /// earlier replay must never be overwritten when one does.
+    fn replay_path(seed: u64) -> String
+    {
+        let mut path = format!("{}/{}.rtr", REPLAY_DIR, seed);
+        let mut count = 1;
+
+        while Path::new(&path).exists()
+        {
+            path = format!("{}/{}-{}.rtr", REPLAY_DIR, seed, count);
+            count += 1;
+        }
+
+        path
+    }
+
+    fn handle_keyboard(&mut self, state: ElementState, keycode: Option<VirtualKeyCode>)
+        -> GameState
+    {
+        let key = match keycode
+        {
+            Some(x) => x,
+            None => return self.tetris.state()
+        };
+
+        let pressed = state == ElementState::Pressed;
+
+        if self.playback.is_some()
+        {
+            if pressed
+            {
+                self.handle_playback_key(key);
+            }
+
+            return GameState::Play;
+        }
+
+        if !pressed
+        {
+            self.keys_down.retain(|x| *x != key);
+            return self.tetris.state();
+        }
+
+        // Ignore the OS key repeat
+        if self.keys_down.contains(&key)
+        {
+            return self.tetris.state();
+        }
+
+        self.keys_down.push(key);
+
+        match self.scene()
+        {
+            Scene::Controls(_) =>
+            {
+                self.handle_controls_key(key);
+                return self.tetris.state();
+            },
+
+            _ if key == CONTROLS_KEY =>
+                return self.apply(Transition::Push(Scene::Controls(ControlsMenu::new()))),
+
+            Scene::InGame => match self.bindings.action(key)
+            {
+                Some(action) => self.press_action(action),
+                None => self.tetris.state()
+            },
+
+            _ =>
+            {
+                let input = MenuInput::from_key(key)
+                    .or_else(|| self.bindings.action(key).and_then(MenuInput::from_action));
+
+                match input
+                {
+                    Some(x) => self.menu_input(x),
+                    None => self.tetris.state()
+                }
+            }
+        }
+    }
+
+    /// Reacts to an action being pressed in game by a key or gamepad
+    fn press_action(&mut self, action: Action) -> GameState
+    {
+        match action
+        {
+            Action::Pause => self.apply(Transition::Push(Scene::Paused { selected: 0 })),
+            Action::Restart => self.apply(Transition::Restart),
+
+            _ =>
+            {
+                match action.button()
+                {
+                    Some(button) => self.pressed.set(button, true),
+                    None => ()
+                }
+
+                self.tetris.state()
+            }
+        }
+    }
+
+    fn handle_controls_key(&mut self, key: VirtualKeyCode)
+    {
+        let open = match self.scenes.last_mut()
+        {
+            Some(&mut Scene::Controls(ref mut x)) => x.handle_key(key, &mut self.bindings),
+            _ => return
+        };
+
+        if !open
+        {
+            self.close_controls();
+        }
+    }
+
+    /// Passes gamepad menu commands to the controls menu
+    fn handle_controls_input(&mut self, input: MenuInput)
+    {
+        let open = match self.scenes.last_mut()
+        {
+            Some(&mut Scene::Controls(ref mut x)) => x.handle_input(input),
+            _ => return
+        };
+
+        if !open
+        {
+            self.close_controls();
+        }
+    }
+
+    /// Leaves the controls menu, saving the bindings
+    fn close_controls(&mut self)
+    {
+        self.scenes.pop();
+
+        match self.bindings.save(CONTROLS_FILE)
+        {
+            Ok(_) => (),
+            Err(e) => eprintln!("Couldn't save controls to {}: {}", CONTROLS_FILE, e)
+        }
+    }
+
+    /// Loads the key bindings, falling back to the defaults
+    fn load_bindings() -> Bindings
+    {
+        match Bindings::load(CONTROLS_FILE)
+        {
+            Ok(x) => x,
+
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Bindings::default(),
+
+            Err(e) =>
+            {
+                eprintln!("Couldn't load controls from {}: {}", CONTROLS_FILE, e);
+                Bindings::default()
+            }
+        }
+    }
+
+    fn handle_playback_key(&mut self, key: VirtualKeyCode)
+    {
+        let seek = SEEK_SECONDS * TICKS_PER_SECOND as usize;
+
+        let playback = match self.playback
+        {
+            Some(ref mut x) => x,
+            None => return
+        };
+
+        let position = playback.position();
+
+        match key
+        {
+            VirtualKeyCode::Space => playback.toggle_pause(),
+            VirtualKeyCode::Period => playback.step_frame(),
+            VirtualKeyCode::Up => playback.faster(),
+            VirtualKeyCode::Down => playback.slower(),
+            VirtualKeyCode::Left => playback.seek(position.saturating_sub(seek)),
+            VirtualKeyCode::Right => playback.seek(position + seek),
+            VirtualKeyCode::Home => playback.seek(0),
+            _ => ()
+        }
+    }
 }
@@ -0,0 +1,468 @@
+use cgmath::Vector2;
+
+use cellmatrix::{Cell, CellMatrix};
+use tetromino::{Shape, Tetromino};
+
+/// The four rotation states of a piece, named after the SRS convention
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Orientation
+{
+    Spawn,
+    Right,
+    Two,
+    Left,
+}
+
+impl Orientation
+{
+    pub fn clockwise(&self) -> Orientation
+    {
+        match *self
+        {
+            Orientation::Spawn => Orientation::Right,
+            Orientation::Right => Orientation::Two,
+            Orientation::Two => Orientation::Left,
+            Orientation::Left => Orientation::Spawn,
+        }
+    }
+
+    pub fn counter_clockwise(&self) -> Orientation
+    {
+        match *self
+        {
+            Orientation::Spawn => Orientation::Left,
+            Orientation::Right => Orientation::Spawn,
+            Orientation::Two => Orientation::Right,
+            Orientation::Left => Orientation::Two,
+        }
+    }
+
+    /// The number of clockwise quarter turns from the spawn orientation
+    pub fn turns(&self) -> u8
+    {
+        match *self
+        {
+            Orientation::Spawn => 0,
+            Orientation::Right => 1,
+            Orientation::Two => 2,
+            Orientation::Left => 3,
+        }
+    }
+}
+
+/// The outcome of a successful rotation
+pub struct Rotation
+{
+    pub matrix: CellMatrix,
+    pub orientation: Orientation,
+    pub position: Vector2<i8>,
+
+    /// Which kick test succeeded, 0 being the unkicked rotation
+    pub kick: usize,
+}
+
+/// Decides where pieces spawn and how they turn.
+///
+/// Every system works on the same matrices from `Tetromino::build_matrix`
+/// rotated with `CellMatrix::rotate_left/rotate_right`. Systems whose pieces
+/// don't turn about the center of their bounding box describe that with
+/// `offset`, which shifts the box in a given orientation.
+pub trait RotationSystem
+{
+    fn spawn_orientation(&self, shape: Shape) -> Orientation;
+
+    /// Position of the bounding box of a freshly spawned piece, offset included
+    fn spawn_position(&self, shape: Shape) -> Vector2<i8>;
+
+    fn offset(&self, _shape: Shape, _orientation: Orientation) -> Vector2<i8>
+    {
+        Vector2::new(0, 0)
+    }
+
+    /// Tries to rotate `piece`, returning None if the rotation is blocked
+    fn rotate(&self, piece: &Tetromino, board: &CellMatrix, clockwise: bool)
+        -> Option<Rotation>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RotationKind
+{
+    /// Guideline Super Rotation System
+    Srs,
+    /// Arika Rotation System, as in TGM
+    Ars,
+    /// Classic NES right-handed rotation
+    Nes,
+}
+
+impl RotationKind
+{
+    pub fn build(&self) -> Box<dyn RotationSystem>
+    {
+        match *self
+        {
+            RotationKind::Srs => Box::new(Srs),
+            RotationKind::Ars => Box::new(Ars),
+            RotationKind::Nes => Box::new(Nes),
+        }
+    }
+}
+
+/// Rotates the piece's matrix without checking the board, and works out the
+/// unkicked position using the system's offsets
+fn rotate_in_place<R: RotationSystem + ?Sized>(system: &R, piece: &Tetromino, clockwise: bool)
+    -> Rotation
+{
+    let mut matrix = piece.matrix.clone();
+
+    let orientation = if clockwise
+    {
+        matrix.rotate_right();
+        piece.orientation.clockwise()
+    }
+    else
+    {
+        matrix.rotate_left();
+        piece.orientation.counter_clockwise()
+    };
+
+    let position = piece.cell_position
+        - system.offset(piece.shape, piece.orientation)
+        + system.offset(piece.shape, orientation);
+
+    Rotation
+    {
+        matrix: matrix,
+        orientation: orientation,
+        position: position,
+        kick: 0,
+    }
+}
+
+// Kick offsets as (x, y) with y pointing *up*, exactly as they appear in the
+// published SRS tables. The board's y axis points down, so callers flip y.
+const JLSTZ_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1,  1), (0, -2), (-1, -2)], // 0 -> R
+    [(0, 0), ( 1, 0), ( 1, -1), (0,  2), ( 1,  2)], // R -> 0
+    [(0, 0), ( 1, 0), ( 1, -1), (0,  2), ( 1,  2)], // R -> 2
+    [(0, 0), (-1, 0), (-1,  1), (0, -2), (-1, -2)], // 2 -> R
+    [(0, 0), ( 1, 0), ( 1,  1), (0, -2), ( 1, -2)], // 2 -> L
+    [(0, 0), (-1, 0), (-1, -1), (0,  2), (-1,  2)], // L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0,  2), (-1,  2)], // L -> 0
+    [(0, 0), ( 1, 0), ( 1,  1), (0, -2), ( 1, -2)], // 0 -> L
+];
+
+const I_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-2, 0), ( 1, 0), (-2, -1), ( 1,  2)], // 0 -> R
+    [(0, 0), ( 2, 0), (-1, 0), ( 2,  1), (-1, -2)], // R -> 0
+    [(0, 0), (-1, 0), ( 2, 0), (-1,  2), ( 2, -1)], // R -> 2
+    [(0, 0), ( 1, 0), (-2, 0), ( 1, -2), (-2,  1)], // 2 -> R
+    [(0, 0), ( 2, 0), (-1, 0), ( 2,  1), (-1, -2)], // 2 -> L
+    [(0, 0), (-2, 0), ( 1, 0), (-2, -1), ( 1,  2)], // L -> 2
+    [(0, 0), ( 1, 0), (-2, 0), ( 1, -2), (-2,  1)], // L -> 0
+    [(0, 0), (-1, 0), ( 2, 0), (-1,  2), ( 2, -1)], // 0 -> L
+];
+
+const O_KICKS: [(i8, i8); 1] = [(0, 0)];
+
+/// Returns the SRS kick offsets to try, in order, when rotating `shape` from
+/// `from` to `to`. Offsets are y-up.
+pub fn srs_kicks(shape: Shape, from: Orientation, to: Orientation) -> &'static [(i8, i8)]
+{
+    let index = match (from, to)
+    {
+        (Orientation::Spawn, Orientation::Right) => 0,
+        (Orientation::Right, Orientation::Spawn) => 1,
+        (Orientation::Right, Orientation::Two) => 2,
+        (Orientation::Two, Orientation::Right) => 3,
+        (Orientation::Two, Orientation::Left) => 4,
+        (Orientation::Left, Orientation::Two) => 5,
+        (Orientation::Left, Orientation::Spawn) => 6,
+        (Orientation::Spawn, Orientation::Left) => 7,
+        _ => return &O_KICKS
+    };
+
+    match shape
+    {
+        Shape::IBlock => &I_KICKS[index],
+        Shape::OBlock => &O_KICKS,
+        _ => &JLSTZ_KICKS[index],
+    }
+}
+
+pub struct Srs;
+
+impl RotationSystem for Srs
+{
+    fn spawn_orientation(&self, _shape: Shape) -> Orientation
+    {
+        Orientation::Spawn
+    }
+
+    fn spawn_position(&self, shape: Shape) -> Vector2<i8>
+    {
+        match shape
+        {
+            Shape::OBlock => Vector2::new(4, 0),
+            _ => Vector2::new(3, 0),
+        }
+    }
+
+    fn rotate(&self, piece: &Tetromino, board: &CellMatrix, clockwise: bool)
+        -> Option<Rotation>
+    {
+        let mut rotation = rotate_in_place(self, piece, clockwise);
+        let origin = rotation.position;
+
+        for (i, &(x, y)) in srs_kicks(piece.shape, piece.orientation, rotation.orientation)
+            .iter().enumerate()
+        {
+            // The kick tables are y-up, the board is y-down
+            rotation.position = origin + Vector2::new(x, -y);
+            rotation.kick = i;
+
+            if !rotation.matrix.collides(board, rotation.position)
+            {
+                return Some(rotation);
+            }
+        }
+
+        None
+    }
+}
+
+/// TGM style rotation. Pieces sit at the bottom of their bounding box and
+/// J, L, S, T and Z spawn flat side up. A blocked rotation tries one cell
+/// right and then one cell left, except for the I piece, which never kicks.
+pub struct Ars;
+
+impl Ars
+{
+    /// The center column rule: a J, L or T whose rotation is first blocked
+    /// in the middle column of its box (scanning in reading order) must not
+    /// kick
+    fn center_column_blocked(rotation: &Rotation, board: &CellMatrix) -> bool
+    {
+        for y in 0..rotation.matrix.height
+        {
+            for x in 0..rotation.matrix.width
+            {
+                match rotation.matrix.get_cell(x, y)
+                {
+                    Cell::Occupied(_) =>
+                    {
+                        let pos = rotation.position + Vector2::new(x as i8, y as i8);
+
+                        if board.is_blocked(pos)
+                        {
+                            return x == 1;
+                        }
+                    },
+
+                    _ => ()
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl RotationSystem for Ars
+{
+    fn spawn_orientation(&self, shape: Shape) -> Orientation
+    {
+        match shape
+        {
+            Shape::IBlock | Shape::OBlock => Orientation::Spawn,
+            _ => Orientation::Two,
+        }
+    }
+
+    fn spawn_position(&self, shape: Shape) -> Vector2<i8>
+    {
+        match shape
+        {
+            Shape::IBlock => Vector2::new(3, 0),
+            Shape::OBlock => Vector2::new(4, 0),
+            _ => Vector2::new(3, -1),
+        }
+    }
+
+    fn offset(&self, shape: Shape, orientation: Orientation) -> Vector2<i8>
+    {
+        match (shape, orientation)
+        {
+            (Shape::IBlock, Orientation::Two) => Vector2::new(0, -1),
+            (Shape::IBlock, Orientation::Left) => Vector2::new(1, 0),
+            (Shape::SBlock, Orientation::Right) => Vector2::new(-1, 0),
+            (Shape::ZBlock, Orientation::Left) => Vector2::new(1, 0),
+            (Shape::OBlock, _) | (Shape::IBlock, _) => Vector2::new(0, 0),
+            (_, Orientation::Spawn) => Vector2::new(0, 1),
+            _ => Vector2::new(0, 0),
+        }
+    }
+
+    fn rotate(&self, piece: &Tetromino, board: &CellMatrix, clockwise: bool)
+        -> Option<Rotation>
+    {
+        let mut rotation = rotate_in_place(self, piece, clockwise);
+
+        if !rotation.matrix.collides(board, rotation.position)
+        {
+            return Some(rotation);
+        }
+
+        match piece.shape
+        {
+            Shape::IBlock | Shape::OBlock => return None,
+
+            Shape::JBlock | Shape::LBlock | Shape::TBlock =>
+            {
+                if Ars::center_column_blocked(&rotation, board)
+                {
+                    return None;
+                }
+            },
+
+            _ => ()
+        }
+
+        let origin = rotation.position;
+
+        for (i, &x) in [1, -1].iter().enumerate()
+        {
+            rotation.position = origin + Vector2::new(x, 0);
+            rotation.kick = i + 1;
+
+            if !rotation.matrix.collides(board, rotation.position)
+            {
+                return Some(rotation);
+            }
+        }
+
+        None
+    }
+}
+
+/// NES rotation. Every piece turns in place with no kicks; S, Z and I only
+/// have two visible states and lean right when vertical.
+pub struct Nes;
+
+impl RotationSystem for Nes
+{
+    fn spawn_orientation(&self, shape: Shape) -> Orientation
+    {
+        match shape
+        {
+            Shape::OBlock => Orientation::Spawn,
+            _ => Orientation::Two,
+        }
+    }
+
+    fn spawn_position(&self, shape: Shape) -> Vector2<i8>
+    {
+        match shape
+        {
+            Shape::OBlock => Vector2::new(4, 0),
+            _ => Vector2::new(3, -1),
+        }
+    }
+
+    fn offset(&self, shape: Shape, orientation: Orientation) -> Vector2<i8>
+    {
+        match (shape, orientation)
+        {
+            (Shape::IBlock, Orientation::Spawn) |
+            (Shape::SBlock, Orientation::Spawn) |
+            (Shape::ZBlock, Orientation::Spawn) => Vector2::new(0, 1),
+
+            (Shape::IBlock, Orientation::Left) |
+            (Shape::SBlock, Orientation::Left) |
+            (Shape::ZBlock, Orientation::Left) => Vector2::new(1, 0),
+
+            _ => Vector2::new(0, 0),
+        }
+    }
+
+    fn rotate(&self, piece: &Tetromino, board: &CellMatrix, clockwise: bool)
+        -> Option<Rotation>
+    {
+        let rotation = rotate_in_place(self, piece, clockwise);
+
+        if rotation.matrix.collides(board, rotation.position)
+        {
+            None
+        }
+        else
+        {
+            Some(rotation)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use cgmath::Vector2;
+
+    use cellmatrix::{Cell, CellMatrix};
+    use tetromino::{Shape, Tetromino};
+    use super::{Ars, Orientation, RotationSystem, Srs};
+
+    fn board() -> CellMatrix
+    {
+        CellMatrix::new(10, 22)
+    }
+
+    #[test]
+    fn srs_kicks_off_the_left_wall()
+    {
+        // Pointing right with its stem against the wall, so turning to point
+        // down needs the second test, one cell right
+        let piece = Tetromino::new(Shape::TBlock, Vector2::new(-1, 10), Orientation::Right);
+        let rotation = Srs.rotate(&piece, &board(), true).unwrap();
+
+        assert_eq!(rotation.orientation, Orientation::Two);
+        assert_eq!(rotation.kick, 1);
+        assert_eq!(rotation.position, Vector2::new(0, 10));
+    }
+
+    #[test]
+    fn srs_i_kicks_off_the_right_wall()
+    {
+        let piece = Tetromino::new(Shape::IBlock, Vector2::new(7, 10), Orientation::Right);
+        let rotation = Srs.rotate(&piece, &board(), true).unwrap();
+
+        assert_eq!(rotation.orientation, Orientation::Two);
+        assert_eq!(rotation.kick, 1);
+        assert_eq!(rotation.position, Vector2::new(6, 10));
+
+        // The I table differs from the JLSTZ one: the same turn of a T by the
+        // wall would be unkicked
+        let piece = Tetromino::new(Shape::TBlock, Vector2::new(7, 10), Orientation::Right);
+        assert_eq!(Srs.rotate(&piece, &board(), true).unwrap().kick, 0);
+    }
+
+    #[test]
+    fn ars_center_column_blocks_kicks()
+    {
+        let piece = Tetromino::new(Shape::JBlock, Vector2::new(4, 10), Orientation::Two);
+
+        // Blocked in the left column first, so it kicks one cell right
+        let mut side = board();
+        side.set_cell(4, 12, Cell::Occupied(Shape::OBlock));
+
+        let rotation = Ars.rotate(&piece, &side, true).unwrap();
+        assert_eq!(rotation.orientation, Orientation::Left);
+        assert_eq!(rotation.kick, 1);
+        assert_eq!(rotation.position, Vector2::new(5, 10));
+
+        // Blocked in the center column first, so it doesn't turn at all,
+        // even though the same kick would fit
+        let mut center = board();
+        center.set_cell(5, 10, Cell::Occupied(Shape::OBlock));
+
+        assert!(Ars.rotate(&piece, &center, true).is_none());
+    }
+}
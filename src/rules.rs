@@ -0,0 +1,162 @@
+use randomizer::RandomizerKind;
+use rotation::RotationKind;
+use scoring::ScoringKind;
+use tetris::TICKS_PER_SECOND;
+
+pub const MAX_PREVIEW: usize = 6;
+
+/// How many times a piece can reset its lock delay under `LockReset::Extended`
+pub const MAX_LOCK_RESETS: u32 = 15;
+
+/// Lines to clear in `GameMode::Sprint`
+pub const SPRINT_LINES: u32 = 40;
+
+/// Length of a `GameMode::Ultra` game, in ticks
+pub const ULTRA_TICKS: u32 = 3 * 60 * TICKS_PER_SECOND;
+
+/// What ends a game besides topping out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameMode
+{
+    /// Play until topping out
+    Marathon,
+    /// Clear `SPRINT_LINES` lines as fast as possible
+    Sprint,
+    /// Score as much as possible in `ULTRA_TICKS`
+    Ultra,
+}
+
+impl GameMode
+{
+    pub fn name(&self) -> &'static str
+    {
+        match *self
+        {
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameMode>
+    {
+        match name
+        {
+            "marathon" => Some(GameMode::Marathon),
+            "sprint" => Some(GameMode::Sprint),
+            "ultra" => Some(GameMode::Ultra),
+            _ => None
+        }
+    }
+}
+
+/// What restarts the lock delay of a piece that has touched down
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockReset
+{
+    /// Moves and rotations reset the delay, up to `MAX_LOCK_RESETS` times
+    /// until the piece reaches a new lowest row
+    Extended,
+    /// Moves and rotations always reset the delay
+    Infinite,
+    /// Only falling to a new row resets the delay
+    Step,
+}
+
+/// Everything that decides how a game plays
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules
+{
+    pub rotation: RotationKind,
+    pub randomizer: RandomizerKind,
+
+    /// Number of upcoming pieces shown, from 0 to `MAX_PREVIEW`
+    pub preview_count: usize,
+
+    pub hold_enabled: bool,
+
+    /// Frames a grounded piece waits before locking. Zero locks the piece as
+    /// soon as gravity fails to move it down, like the NES.
+    pub lock_delay: u32,
+    pub lock_reset: LockReset,
+
+    /// Delayed Auto Shift: frames a direction is held before it repeats
+    pub das: u32,
+    /// Auto Repeat Rate: frames between repeated moves. Zero moves the piece
+    /// straight to the wall.
+    pub arr: u32,
+
+    /// Multiplies gravity while soft drop is held
+    pub soft_drop_factor: f32,
+
+    pub scoring: ScoringKind,
+
+    /// Ends the game when any cell of a piece locks in the hidden rows, not
+    /// just when the whole piece does
+    pub partial_lock_out: bool,
+
+    pub mode: GameMode,
+}
+
+impl Rules
+{
+    /// Modern guideline rules
+    pub fn guideline() -> Rules
+    {
+        Rules
+        {
+            rotation: RotationKind::Srs,
+            randomizer: RandomizerKind::SevenBag,
+            preview_count: 3,
+            hold_enabled: true,
+            lock_delay: 30,
+            lock_reset: LockReset::Extended,
+            das: 10,
+            arr: 2,
+            soft_drop_factor: 20.0,
+            scoring: ScoringKind::Guideline,
+            partial_lock_out: false,
+            mode: GameMode::Marathon,
+        }
+    }
+
+    /// TGM style arcade rules
+    pub fn arcade() -> Rules
+    {
+        Rules
+        {
+            rotation: RotationKind::Ars,
+            randomizer: RandomizerKind::History,
+            preview_count: 1,
+            hold_enabled: false,
+            lock_delay: 30,
+            lock_reset: LockReset::Step,
+            das: 16,
+            arr: 1,
+            soft_drop_factor: 20.0,
+            scoring: ScoringKind::Guideline,
+            partial_lock_out: false,
+            mode: GameMode::Marathon,
+        }
+    }
+
+    /// NES style rules
+    pub fn classic() -> Rules
+    {
+        Rules
+        {
+            rotation: RotationKind::Nes,
+            randomizer: RandomizerKind::Nes,
+            preview_count: 1,
+            hold_enabled: false,
+            lock_delay: 0,
+            lock_reset: LockReset::Step,
+            das: 16,
+            arr: 6,
+            soft_drop_factor: 24.0,
+            scoring: ScoringKind::Nes,
+            partial_lock_out: false,
+            mode: GameMode::Marathon,
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use glium::glutin::VirtualKeyCode;
+
+use bindings::{Action, Bindings, ControlsMenu, ACTIONS};
+use randomizer::RandomizerKind;
+use renderer::Renderer;
+use rotation::RotationKind;
+use rules::{GameMode, Rules, MAX_PREVIEW};
+use sprite::DrawContext;
+use tetris::{Tetris, TICKS_PER_SECOND};
+
+/// How long the game over screen shows before the results
+const GAME_OVER_TICKS: u32 = 2 * TICKS_PER_SECOND;
+
+/// Largest DAS and ARR settable from the options menu
+const MAX_DAS: u32 = 30;
+const MAX_ARR: u32 = 10;
+
+const TITLE_ENTRIES: [&'static str; 3] = ["Play", "Options", "Quit"];
+const MODES: [GameMode; 3] = [GameMode::Marathon, GameMode::Sprint, GameMode::Ultra];
+const OPTION_COUNT: usize = 7;
+const PAUSE_ENTRIES: [&'static str; 4] = ["Resume", "Restart", "Controls", "Quit to title"];
+const RESULTS_ENTRIES: [&'static str; 2] = ["Play again", "Title"];
+
+const ROTATIONS: [(RotationKind, &'static str); 3] = [
+    (RotationKind::Srs, "SRS"),
+    (RotationKind::Ars, "ARS"),
+    (RotationKind::Nes, "NES"),
+];
+
+const RANDOMIZERS: [(RandomizerKind, &'static str); 4] = [
+    (RandomizerKind::SevenBag, "7-bag"),
+    (RandomizerKind::PureRandom, "random"),
+    (RandomizerKind::History, "history"),
+    (RandomizerKind::Nes, "NES"),
+];
+
+/// A menu command, from a key or the gamepad
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MenuInput
+{
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+}
+
+impl MenuInput
+{
+    /// The fixed menu keys, which work whatever the bindings are
+    pub fn from_key(key: VirtualKeyCode) -> Option<MenuInput>
+    {
+        match key
+        {
+            VirtualKeyCode::Up => Some(MenuInput::Up),
+            VirtualKeyCode::Down => Some(MenuInput::Down),
+            VirtualKeyCode::Left => Some(MenuInput::Left),
+            VirtualKeyCode::Right => Some(MenuInput::Right),
+            VirtualKeyCode::Return => Some(MenuInput::Confirm),
+            VirtualKeyCode::Escape | VirtualKeyCode::Back => Some(MenuInput::Back),
+            _ => None
+        }
+    }
+
+    /// Lets the game controls drive menus too, which is all a gamepad has
+    pub fn from_action(action: Action) -> Option<MenuInput>
+    {
+        match action
+        {
+            Action::HardDrop => Some(MenuInput::Up),
+            Action::SoftDrop => Some(MenuInput::Down),
+            Action::MoveLeft => Some(MenuInput::Left),
+            Action::MoveRight => Some(MenuInput::Right),
+            Action::RotateCW => Some(MenuInput::Confirm),
+            Action::RotateCCW | Action::Pause => Some(MenuInput::Back),
+            _ => None
+        }
+    }
+}
+
+/// What a scene asks of the window
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Transition
+{
+    Stay,
+    Push(Scene),
+    Pop,
+    /// Swaps the top scene for another
+    Replace(Scene),
+    StartGame(GameMode),
+    /// Starts another game with the same rules
+    Restart,
+    ToTitle,
+    Quit,
+}
+
+/// One screen of the window. The window keeps a stack of them and only the
+/// top one gets input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scene
+{
+    Title { selected: usize },
+    ModeSelect { selected: usize },
+    /// Rules for the next game
+    Options { selected: usize },
+    /// Takes raw keys, since it binds them
+    Controls(ControlsMenu),
+    InGame,
+    Paused { selected: usize },
+    GameOver { ticks: u32 },
+    Results { selected: usize },
+}
+
+impl Scene
+{
+    pub fn title() -> Scene
+    {
+        Scene::Title { selected: 0 }
+    }
+
+    /// Whether the game runs while this scene is on top
+    pub fn runs_game(&self) -> bool
+    {
+        *self == Scene::InGame
+    }
+
+    /// Whether the board is drawn under this scene. The pause screen hides
+    /// it so pausing can't be used to plan ahead.
+    pub fn shows_board(&self) -> bool
+    {
+        match *self
+        {
+            Scene::InGame | Scene::GameOver { .. } => true,
+            _ => false
+        }
+    }
+
+    /// Draws the scene, over the board if `shows_board` says so
+    pub fn draw(&self, renderer: &mut Renderer, context: &mut DrawContext, rules: &Rules,
+        tetris: &Tetris, bindings: &Bindings)
+    {
+        match *self
+        {
+            Scene::Title { selected } =>
+                renderer.draw_menu(context, "Rustris", &strings(&TITLE_ENTRIES), Some(selected)),
+
+            Scene::ModeSelect { selected } =>
+            {
+                let modes: Vec<String> = MODES.iter().map(|x| mode_description(*x)).collect();
+                renderer.draw_menu(context, "Mode", &modes, Some(selected));
+            },
+
+            Scene::Options { selected } =>
+                renderer.draw_menu(context, "Options", &option_lines(rules), Some(selected)),
+
+            Scene::Controls(ref menu) =>
+            {
+                let lines: Vec<String> = ACTIONS.iter().map(|x|
+                    if menu.is_waiting() && *x == menu.selected()
+                    {
+                        format!("{}: press a key or button", x.name())
+                    }
+                    else
+                    {
+                        format!("{}: {}", x.name(), bindings.describe(*x))
+                    }).collect();
+
+                let selected = ACTIONS.iter().position(|x| *x == menu.selected());
+
+                renderer.draw_menu(context, "Controls", &lines, selected);
+                renderer.draw_entries(context, lines.len() + 1,
+                    &[format!("Return: rebind  Back: clear  Esc: done")], None);
+            },
+
+            Scene::InGame => (),
+
+            Scene::Paused { selected } =>
+                renderer.draw_menu(context, "Paused", &strings(&PAUSE_ENTRIES), Some(selected)),
+
+            Scene::GameOver { .. } =>
+            {
+                let heading = if tetris.is_goal_reached() { "Finished" } else { "Game over" };
+                renderer.draw_menu(context, heading, &[], None);
+            },
+
+            Scene::Results { selected } =>
+            {
+                let stats = result_lines(tetris);
+
+                renderer.draw_menu(context, "Results", &stats, None);
+                renderer.draw_entries(context, stats.len() + 1, &strings(&RESULTS_ENTRIES),
+                    Some(selected));
+            }
+        }
+    }
+
+    /// Advances timers by a tick
+    pub fn update(&mut self) -> Transition
+    {
+        match *self
+        {
+            Scene::GameOver { ref mut ticks } =>
+            {
+                *ticks += 1;
+
+                if *ticks >= GAME_OVER_TICKS
+                {
+                    return Transition::Replace(Scene::Results { selected: 0 });
+                }
+            },
+
+            _ => ()
+        }
+
+        Transition::Stay
+    }
+
+    /// Handles a menu command. The options menu edits `rules`.
+    pub fn handle(&mut self, input: MenuInput, rules: &mut Rules) -> Transition
+    {
+        match *self
+        {
+            Scene::Title { ref mut selected } =>
+                match menu(selected, TITLE_ENTRIES.len(), input)
+                {
+                    Some(0) => Transition::Push(Scene::ModeSelect { selected: 0 }),
+                    Some(1) => Transition::Push(Scene::Options { selected: 0 }),
+                    Some(_) => Transition::Quit,
+                    None => Transition::Stay
+                },
+
+            Scene::ModeSelect { ref mut selected } =>
+                match menu(selected, MODES.len(), input)
+                {
+                    Some(x) => Transition::StartGame(MODES[x]),
+                    None if input == MenuInput::Back => Transition::Pop,
+                    None => Transition::Stay
+                },
+
+            Scene::Options { ref mut selected } =>
+            {
+                let step = match input
+                {
+                    MenuInput::Left => -1,
+                    MenuInput::Right => 1,
+                    MenuInput::Back => return Transition::Pop,
+                    _ => 0
+                };
+
+                match menu(selected, OPTION_COUNT, input)
+                {
+                    Some(6) => return Transition::Push(Scene::Controls(ControlsMenu::new())),
+                    _ => ()
+                }
+
+                match *selected
+                {
+                    0 => rules.rotation = cycle(&ROTATIONS, rules.rotation, step),
+                    1 => rules.randomizer = cycle(&RANDOMIZERS, rules.randomizer, step),
+                    2 => rules.preview_count = adjust(rules.preview_count as u32, step,
+                        MAX_PREVIEW as u32) as usize,
+                    3 => rules.hold_enabled ^= step != 0,
+                    4 => rules.das = adjust(rules.das, step, MAX_DAS),
+                    5 => rules.arr = adjust(rules.arr, step, MAX_ARR),
+                    _ => ()
+                }
+
+                Transition::Stay
+            },
+
+            Scene::Paused { ref mut selected } =>
+                match menu(selected, PAUSE_ENTRIES.len(), input)
+                {
+                    Some(0) => Transition::Pop,
+                    Some(1) => Transition::Restart,
+                    Some(2) => Transition::Push(Scene::Controls(ControlsMenu::new())),
+                    Some(_) => Transition::ToTitle,
+                    None if input == MenuInput::Back => Transition::Pop,
+                    None => Transition::Stay
+                },
+
+            Scene::GameOver { .. } =>
+                match input
+                {
+                    MenuInput::Confirm | MenuInput::Back =>
+                        Transition::Replace(Scene::Results { selected: 0 }),
+                    _ => Transition::Stay
+                },
+
+            Scene::Results { ref mut selected } =>
+                match menu(selected, RESULTS_ENTRIES.len(), input)
+                {
+                    Some(0) => Transition::Restart,
+                    Some(_) => Transition::ToTitle,
+                    None if input == MenuInput::Back => Transition::ToTitle,
+                    None => Transition::Stay
+                },
+
+            Scene::Controls(_) | Scene::InGame => Transition::Stay,
+        }
+    }
+}
+
+/// Moves the selection of a vertical menu. Returns the entry confirmed, if
+/// any.
+fn menu(selected: &mut usize, len: usize, input: MenuInput) -> Option<usize>
+{
+    match input
+    {
+        MenuInput::Up => *selected = (*selected + len - 1) % len,
+        MenuInput::Down => *selected = (*selected + 1) % len,
+        MenuInput::Confirm => return Some(*selected),
+        _ => ()
+    }
+
+    None
+}
+
+fn strings(entries: &[&'static str]) -> Vec<String>
+{
+    entries.iter().map(|x| x.to_string()).collect()
+}
+
+fn name<T: PartialEq + Copy>(list: &[(T, &'static str)], value: T) -> &'static str
+{
+    list.iter().find(|x| x.0 == value).map(|x| x.1).unwrap_or("?")
+}
+
+/// Steps through the values of `list`, wrapping around
+fn cycle<T: PartialEq + Copy>(list: &[(T, &'static str)], value: T, step: i32) -> T
+{
+    let index = list.iter().position(|x| x.0 == value).unwrap_or(0) as i32;
+    let len = list.len() as i32;
+
+    list[((index + step + len) % len) as usize].0
+}
+
+fn adjust(value: u32, step: i32, max: u32) -> u32
+{
+    match step
+    {
+        -1 => value.saturating_sub(1),
+        1 if value < max => value + 1,
+        _ => value
+    }
+}
+
+fn option_lines(rules: &Rules) -> Vec<String>
+{
+    vec![
+        format!("Rotation: {}", name(&ROTATIONS, rules.rotation)),
+        format!("Randomizer: {}", name(&RANDOMIZERS, rules.randomizer)),
+        format!("Preview: {}", rules.preview_count),
+        format!("Hold: {}", if rules.hold_enabled { "on" } else { "off" }),
+        format!("DAS: {} frames", rules.das),
+        format!("ARR: {} frames", rules.arr),
+        format!("Controls"),
+    ]
+}
+
+/// How the finished game went, for the results screen
+fn result_lines(tetris: &Tetris) -> Vec<String>
+{
+    let score = tetris.score();
+
+    vec![
+        mode_description(tetris.rules().mode),
+        format!("Score: {}", score.score),
+        format!("Lines: {}", score.lines),
+        format!("Level: {}", score.level),
+        format!("Pieces: {}", tetris.pieces()),
+        format!("Time: {}", format_time(tetris.ticks())),
+    ]
+}
+
+fn mode_description(mode: GameMode) -> String
+{
+    match mode
+    {
+        GameMode::Marathon => format!("Marathon: play until you top out"),
+        GameMode::Sprint => format!("Sprint: clear 40 lines"),
+        GameMode::Ultra => format!("Ultra: score in 3 minutes"),
+    }
+}
+
+/// Formats ticks as m:ss.cc
+pub fn format_time(ticks: u32) -> String
+{
+    let centiseconds = ticks as u64 * 100 / TICKS_PER_SECOND as u64;
+
+    format!("{}:{:02}.{:02}", centiseconds / 6000, centiseconds / 100 % 60, centiseconds % 100)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use rotation::RotationKind;
+    use rules::{GameMode, Rules};
+    use super::{MenuInput, Scene, Transition};
+
+    #[test]
+    fn menus_lead_to_a_game()
+    {
+        let mut rules = Rules::guideline();
+        let mut title = Scene::title();
+
+        assert_eq!(title.handle(MenuInput::Confirm, &mut rules),
+            Transition::Push(Scene::ModeSelect { selected: 0 }));
+
+        let mut modes = Scene::ModeSelect { selected: 0 };
+        modes.handle(MenuInput::Up, &mut rules);
+
+        assert_eq!(modes.handle(MenuInput::Confirm, &mut rules),
+            Transition::StartGame(GameMode::Ultra));
+        assert_eq!(modes.handle(MenuInput::Back, &mut rules), Transition::Pop);
+    }
+
+    #[test]
+    fn options_edit_rules()
+    {
+        let mut rules = Rules::guideline();
+        let mut options = Scene::Options { selected: 0 };
+
+        options.handle(MenuInput::Left, &mut rules);
+        assert_eq!(rules.rotation, RotationKind::Nes);
+
+        options.handle(MenuInput::Down, &mut rules);
+        options.handle(MenuInput::Down, &mut rules);
+        options.handle(MenuInput::Down, &mut rules);
+        options.handle(MenuInput::Right, &mut rules);
+        assert!(!rules.hold_enabled);
+
+        options.handle(MenuInput::Down, &mut rules);
+        options.handle(MenuInput::Left, &mut rules);
+        assert_eq!(rules.das, Rules::guideline().das - 1);
+
+        assert_eq!(options.handle(MenuInput::Back, &mut rules), Transition::Pop);
+    }
+}
@@ -0,0 +1,376 @@
+use tspin::TSpin;
+
+/// Which scoring and speed rules a game uses
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScoringKind
+{
+    /// Modern guideline scoring with hard drop points and the guideline
+    /// gravity curve
+    Guideline,
+    /// NES scoring and speed table
+    Nes,
+}
+
+/// Lines needed to go up a level
+pub const LINES_PER_LEVEL: u32 = 10;
+
+/// The fastest gravity we bother simulating, in rows per frame
+pub const MAX_GRAVITY: f32 = 20.0;
+
+/// Rows per frame for each guideline level, from level 1. Worked out ahead
+/// of time from seconds per row = (0.8 - (level - 1) * 0.007) ^ (level - 1)
+/// so the speed doesn't depend on the platform's `powf`. The curve stops
+/// getting faster after the last level.
+const GUIDELINE_GRAVITY: [f32; 20] = [
+    0.016667, 0.021017, 0.026978, 0.035256, 0.046922,
+    0.063612, 0.087869, 0.1237, 0.177527, 0.259801,
+    0.387811, 0.590646, 0.918105, 1.45696, 2.36118,
+    3.909099, 6.613536, 11.437941, 20.228823, 36.598047,
+];
+
+/// Frames per row for each NES level, from level 0
+const NES_FRAMES_PER_ROW: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+    5, 5, 5, 4, 4, 4, 3, 3, 3, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 1,
+];
+
+/// Garbage sent for each combo count, capped at the last entry
+const COMBO_GARBAGE: [u32; 13] = [0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Garbage sent on top of everything else for a perfect clear
+const PERFECT_CLEAR_GARBAGE: u32 = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Score
+{
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+}
+
+/// What a single piece locking was worth
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LockResult
+{
+    pub lines: u32,
+    pub tspin: TSpin,
+
+    /// How many line clearing locks in a row came before this one, or None
+    /// if this lock didn't clear anything
+    pub combo: Option<u32>,
+
+    /// Whether this clear followed another tetris or T-spin clear
+    pub back_to_back: bool,
+
+    /// Whether the board was left empty
+    pub perfect_clear: bool,
+
+    pub points: u32,
+    pub garbage: u32,
+}
+
+/// Keeps score, lines and level for a game
+pub struct Scoring
+{
+    kind: ScoringKind,
+    first_level: u32,
+    score: Score,
+
+    combo: Option<u32>,
+    /// Whether the last clear was a tetris or a T-spin
+    difficult: bool,
+}
+
+impl Scoring
+{
+    pub fn new(kind: ScoringKind) -> Scoring
+    {
+        let first_level = match kind
+        {
+            ScoringKind::Guideline => 1,
+            ScoringKind::Nes => 0,
+        };
+
+        Scoring
+        {
+            kind: kind,
+            first_level: first_level,
+            score: Score
+            {
+                score: 0,
+                lines: 0,
+                level: first_level,
+            },
+
+            combo: None,
+            difficult: false,
+        }
+    }
+
+    pub fn score(&self) -> &Score
+    {
+        &self.score
+    }
+
+    /// Awards points for a piece locking and clearing `lines` lines at the
+    /// current level, then levels up if needed
+    pub fn locked(&mut self, lines: u32, tspin: TSpin, perfect_clear: bool) -> LockResult
+    {
+        let level = self.score.level;
+
+        // Clears that don't clear anything neither continue nor break
+        // back-to-back
+        let difficult = lines == 4 || (lines > 0 && tspin != TSpin::None);
+        let back_to_back = difficult && self.difficult;
+
+        if lines > 0
+        {
+            self.difficult = difficult;
+            self.combo = Some(self.combo.map_or(0, |x| x + 1));
+        }
+        else
+        {
+            self.combo = None;
+        }
+
+        let mut points = match (self.kind, tspin)
+        {
+            (ScoringKind::Guideline, TSpin::None) =>
+                [0, 100, 300, 500, 800][lines as usize] * level,
+
+            (ScoringKind::Guideline, TSpin::Mini) =>
+                [100, 200, 400, 400][lines as usize] * level,
+
+            (ScoringKind::Guideline, TSpin::Full) =>
+                [400, 800, 1200, 1600][lines as usize] * level,
+
+            // The NES has no idea what a T-spin is
+            (ScoringKind::Nes, _) =>
+                [0, 40, 100, 300, 1200][lines as usize] * (level + 1),
+        };
+
+        if self.kind == ScoringKind::Guideline
+        {
+            if back_to_back
+            {
+                points += points / 2;
+            }
+
+            points += 50 * self.combo.unwrap_or(0) * level;
+
+            if perfect_clear
+            {
+                points += if back_to_back && lines == 4
+                {
+                    3200 * level
+                }
+                else
+                {
+                    [0, 800, 1200, 1800, 2000][lines as usize] * level
+                };
+            }
+        }
+
+        self.score.score += points;
+        self.score.lines += lines;
+        self.score.level = self.first_level + self.score.lines / LINES_PER_LEVEL;
+
+        LockResult
+        {
+            lines: lines,
+            tspin: tspin,
+            combo: if lines > 0 { self.combo } else { None },
+            back_to_back: back_to_back,
+            perfect_clear: perfect_clear,
+            points: points,
+            garbage: Scoring::garbage(lines, tspin, self.combo, back_to_back, perfect_clear),
+        }
+    }
+
+    /// Lines of garbage a lock sends to an opponent
+    fn garbage(lines: u32, tspin: TSpin, combo: Option<u32>, back_to_back: bool,
+        perfect_clear: bool) -> u32
+    {
+        if lines == 0
+        {
+            return 0;
+        }
+
+        let mut garbage = match tspin
+        {
+            TSpin::None => [0, 0, 1, 2, 4][lines as usize],
+            TSpin::Mini => [0, 0, 1, 1][lines as usize],
+            TSpin::Full => lines * 2,
+        };
+
+        if back_to_back
+        {
+            garbage += 1;
+        }
+
+        let combo = combo.unwrap_or(0) as usize;
+        garbage += COMBO_GARBAGE[::std::cmp::min(combo, COMBO_GARBAGE.len() - 1)];
+
+        if perfect_clear
+        {
+            garbage += PERFECT_CLEAR_GARBAGE;
+        }
+
+        garbage
+    }
+
+    pub fn soft_drop(&mut self, rows: u32)
+    {
+        self.score.score += rows;
+    }
+
+    pub fn hard_drop(&mut self, rows: u32)
+    {
+        match self.kind
+        {
+            ScoringKind::Guideline => self.score.score += rows * 2,
+            ScoringKind::Nes => (),
+        }
+    }
+
+    /// Gravity for the current level, in rows per frame
+    pub fn gravity(&self) -> f32
+    {
+        let level = self.score.level;
+
+        let gravity = match self.kind
+        {
+            ScoringKind::Guideline =>
+            {
+                let index = ::std::cmp::min(level.saturating_sub(1) as usize,
+                    GUIDELINE_GRAVITY.len() - 1);
+
+                GUIDELINE_GRAVITY[index]
+            },
+
+            ScoringKind::Nes =>
+            {
+                let index = ::std::cmp::min(level as usize, NES_FRAMES_PER_ROW.len() - 1);
+
+                1.0 / NES_FRAMES_PER_ROW[index] as f32
+            },
+        };
+
+        if gravity > MAX_GRAVITY { MAX_GRAVITY } else { gravity }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use tspin::TSpin;
+    use super::{Scoring, ScoringKind, MAX_GRAVITY};
+
+    #[test]
+    fn guideline_gravity_follows_the_table()
+    {
+        let mut scoring = Scoring::new(ScoringKind::Guideline);
+        let mut last = 0.0;
+
+        assert_eq!(scoring.gravity(), 0.016667);
+
+        for level in 1..30
+        {
+            scoring.score.level = level;
+            let gravity = scoring.gravity();
+
+            assert!(gravity >= last);
+            assert!(gravity <= MAX_GRAVITY);
+            last = gravity;
+        }
+
+        assert_eq!(last, MAX_GRAVITY);
+    }
+
+    #[test]
+    fn combos_build_until_a_lock_clears_nothing()
+    {
+        let mut scoring = Scoring::new(ScoringKind::Guideline);
+
+        let combos: Vec<_> = [1, 1, 2, 0, 1].iter()
+            .map(|&x| scoring.locked(x, TSpin::None, false))
+            .map(|x| (x.combo, x.points))
+            .collect();
+
+        assert_eq!(combos, vec![(Some(0), 100), (Some(1), 150), (Some(2), 400), (None, 0),
+            (Some(0), 100)]);
+    }
+
+    #[test]
+    fn back_to_back_until_an_easy_clear()
+    {
+        let mut scoring = Scoring::new(ScoringKind::Guideline);
+
+        let first = scoring.locked(4, TSpin::None, false);
+        assert!(!first.back_to_back);
+        assert_eq!(first.points, 800);
+
+        // Locks that clear nothing keep the chain going, and also stop the
+        // combo so it doesn't add to the points
+        scoring.locked(0, TSpin::None, false);
+
+        let second = scoring.locked(4, TSpin::None, false);
+        assert!(second.back_to_back);
+        assert_eq!(second.points, 1200);
+
+        scoring.locked(0, TSpin::None, false);
+
+        let tspin = scoring.locked(1, TSpin::Full, false);
+        assert!(tspin.back_to_back);
+        assert_eq!(tspin.points, 1200);
+
+        scoring.locked(0, TSpin::None, false);
+
+        let single = scoring.locked(1, TSpin::None, false);
+        assert!(!single.back_to_back);
+        assert_eq!(single.points, 100);
+
+        scoring.locked(0, TSpin::None, false);
+
+        // Ten lines in, so this one is worth level 2 points
+        let broken = scoring.locked(4, TSpin::None, false);
+        assert!(!broken.back_to_back);
+        assert_eq!(broken.points, 1600);
+    }
+
+    #[test]
+    fn perfect_clear_bonus()
+    {
+        let mut scoring = Scoring::new(ScoringKind::Guideline);
+
+        let single = scoring.locked(1, TSpin::None, true);
+        assert_eq!((single.points, single.garbage), (900, 10));
+
+        scoring.locked(0, TSpin::None, false);
+        scoring.locked(4, TSpin::None, false);
+        scoring.locked(0, TSpin::None, false);
+
+        // A back-to-back tetris perfect clear gets its own bonus
+        let tetris = scoring.locked(4, TSpin::None, true);
+        assert!(tetris.back_to_back);
+        assert_eq!((tetris.points, tetris.garbage), (4400, 15));
+    }
+
+    #[test]
+    fn nes_scoring_has_no_bonuses()
+    {
+        let mut scoring = Scoring::new(ScoringKind::Nes);
+
+        let points: Vec<_> = [(4, TSpin::None, false), (4, TSpin::None, false),
+            (1, TSpin::Full, false), (1, TSpin::None, true), (1, TSpin::None, false)].iter()
+            .map(|&(lines, tspin, perfect_clear)| scoring.locked(lines, tspin, perfect_clear))
+            .map(|x| x.points)
+            .collect();
+
+        // Level 0 is worth level 1 points, and the tenth line goes up a level
+        assert_eq!(points, vec![1200, 1200, 40, 40, 80]);
+        assert_eq!(scoring.score().level, 1);
+        assert_eq!(scoring.score().lines, 11);
+    }
+}
@@ -6,7 +6,8 @@ in vec2 v_tex_coords;
 out vec4 color;
 
 uniform sampler2D tex;
+uniform vec4 tint;
 
 void main() {
-    color = v_color * texture(tex, v_tex_coords);
+    color = tint * v_color * texture(tex, v_tex_coords);
 }
@@ -11,6 +11,23 @@ use rect::Rect;
 use rootwindow::Vertex;
 use spritemanager::{SpriteManager, Textures};
 
+/// What drawing takes, gathered once a frame
+pub struct DrawContext<'a>
+{
+    pub target: &'a mut glium::Frame,
+    pub program: &'a glium::Program,
+    pub sprite_manager: &'a SpriteManager,
+    pub projection: &'a Matrix4<f32>,
+}
+
+impl<'a> DrawContext<'a>
+{
+    pub fn draw(&mut self, sprite: &Sprite)
+    {
+        sprite.draw(self.target, self.program, self.sprite_manager, self.projection);
+    }
+}
+
 pub struct Sprite
 {
     pub texture: Textures,
@@ -18,6 +35,8 @@ pub struct Sprite
     pub position: Vector3<f32>,
     pub rotation: f32,
     pub tint: [f32; 4],
+    /// Grows the sprite right and down from its position
+    pub scale: f32,
 
     vertex_buffer: glium::VertexBuffer<Vertex>,
     index_buffer: glium::IndexBuffer<u16>,
@@ -36,33 +55,49 @@ impl Sprite
         texture: Textures, rect: Rect, position: Vector2<f32>, tint: [f32; 4])
         -> io::Result<Sprite>
     {
+        let mut sprite = try!(Sprite::new_region(display, texture, rect,
+            Rect::new(0.0, 0.0, 1.0, 1.0), position));
+
+        sprite.set_tint(tint);
+        Ok(sprite)
+    }
+
+    /// A sprite showing part of its texture. `region` is in texture
+    /// coordinates, from (0, 0) at the top left to (1, 1) at the bottom right.
+    pub fn new_region(display: &glium::backend::glutin_backend::GlutinFacade,
+        texture: Textures, rect: Rect, region: Rect, position: Vector2<f32>)
+        -> io::Result<Sprite>
+    {
+        let white = [1.0, 1.0, 1.0, 1.0];
+
+        // Textures are stored bottom row first, so v is flipped
         let vertex_buffer = glium::VertexBuffer::new(display,
             vec![
                 Vertex
                 {
                     position: [rect.left(), rect.top()],
-                    color: tint,
-                    tex_coords: [0.0, 1.0],
+                    color: white,
+                    tex_coords: [region.left(), 1.0 - region.top()],
                 },
 
                 Vertex
                 {
                     position: [rect.right(), rect.top()],
-                    color: tint,
-                    tex_coords: [1.0, 1.0],
+                    color: white,
+                    tex_coords: [region.right(), 1.0 - region.top()],
                 },
 
                 Vertex
                 {
                     position: [rect.right(), rect.bottom()],
-                    color: tint,
-                    tex_coords: [1.0, 0.0],
+                    color: white,
+                    tex_coords: [region.right(), 1.0 - region.bottom()],
                 },
 
                 Vertex {
                     position: [rect.left(), rect.bottom()],
-                    color: tint,
-                    tex_coords: [0.0, 0.0],
+                    color: white,
+                    tex_coords: [region.left(), 1.0 - region.bottom()],
                 },
             ]
         );
@@ -78,7 +113,8 @@ impl Sprite
 
             position: cgmath::zero(),
             rotation: 0.0,
-            tint: tint,
+            tint: white,
+            scale: 1.0,
 
             vertex_buffer: vertex_buffer,
             index_buffer: index_buffer,
@@ -97,7 +133,15 @@ impl Sprite
 
         let translation = Matrix4::from_translation(&self.position);
 
-        let model =  translation * rotmatrix;
+        let s = self.scale;
+        let scale = Matrix4::new(
+            s, 0.0, 0.0, 0.0,
+            0.0, s, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        );
+
+        let model =  translation * rotmatrix * scale;
         
         let tex_id = self.texture;
         let ref texture = sprite_manager.get_texture(tex_id);
@@ -120,6 +164,7 @@ impl Sprite
                     &uniform!
                     {
                         matrix: *projection * model,
+                        tint: self.tint,
                         tex: texture
                             .sampled()
                             .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
@@ -137,4 +182,14 @@ impl Sprite
     {
         self.rotation = rotation;
     }
+
+    pub fn set_tint(&mut self, tint: [f32; 4])
+    {
+        self.tint = tint;
+    }
+
+    pub fn set_scale(&mut self, scale: f32)
+    {
+        self.scale = scale;
+    }
 }
@@ -12,6 +12,7 @@ pub enum Textures
 {
     SpriteSheet = 0,
     Background,
+    Font,
 }
 
 pub struct SpriteManager
@@ -34,11 +35,17 @@ impl SpriteManager
             image::PNG
         ).unwrap();
 
+        let font = image::load(
+            io::Cursor::new(&include_bytes!("../images/font.png")[..]),
+            image::PNG
+        ).unwrap();
+
         SpriteManager
         {
             textures: vec![
                 texture::Texture2d::new(&root_window.display, sprite_sheet),
                 texture::Texture2d::new(&root_window.display, background),
+                texture::Texture2d::new(&root_window.display, font),
             ]
         }
     }
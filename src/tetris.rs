@@ -1,266 +1,1096 @@
-use cgmath::{Vector2, Matrix4};
+use std::mem;
 
-use glium;
-use glium::glutin::{Event, ElementState, VirtualKeyCode};
+use cgmath::Vector2;
 
 use cellmatrix::CellMatrix;
-use rect::Rect;
-use rootwindow::GameState;
-use sprite::Sprite;
-use spritemanager::{SpriteManager, Textures};
-use tetromino::{Tetromino, Shape};
+use events::{GameEvent, TopOut};
+use input::{Button, InputFrame};
+use randomizer::Randomizer;
+use rotation::RotationSystem;
+use rules::{GameMode, LockReset, Rules, MAX_LOCK_RESETS, MAX_PREVIEW, SPRINT_LINES, ULTRA_TICKS};
+use scoring::{LockResult, Score, Scoring, MAX_GRAVITY};
+use tetromino::{Shape, Tetromino};
+use tspin::{self, TSpin};
 
 
-const BOARD_POS: Vector2<f32> = Vector2 { x: 36.5, y: 8.5 };
+/// Rows at the top of the board that are above the visible field
+pub const HIDDEN_ROWS: i8 = 2;
 
+/// How many times `step` is called per second of game time
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// Events kept around for a UI that isn't draining them
+const MAX_EVENTS: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameState
+{
+    Exit,
+    Play,
+    /// The stack topped out or the goal of the mode was reached. The game
+    /// keeps drawing until it is restarted.
+    GameOver,
+}
+
+/// The game itself: board, active piece, queue, hold, score and timers. It
+/// knows nothing about windows or drawing, see `Renderer` for that.
+///
+/// The game only moves forward through `step`, so the same rules, seed and
+/// input frames always play out the same way.
 pub struct Tetris
 {
-    width: u32,
-    height: u32,
+    /// The buttons held on the previous step
+    inputs: InputFrame,
+    /// The direction being auto shifted, -1 for left, 1 for right or 0
+    shift_direction: i8,
+    das_frames: u32,
+    arr_frames: u32,
+
+    rules: Rules,
+    rotation_system: Box<dyn RotationSystem>,
+
+    seed: u64,
+    randomizer: Box<dyn Randomizer>,
+    queue: Vec<Shape>,
 
-    key_held: Option<VirtualKeyCode>,
+    held: Option<Shape>,
+    hold_used: bool,
 
     board: CellMatrix,
+    current_tetromino: Option<Tetromino>,
+
+    scoring: Scoring,
 
-    old_gravity: f32,
+    soft_drop: bool,
     gravity: f32,
-    gravity_frame: u32,
+    gravity_acc: f32,
 
-    background: Option<Sprite>,
-    tetrominos: Vec<Tetromino>,
-    current_tetromino: Option<Tetromino>,
+    lock_frame: u32,
+    lock_resets: u32,
+    lowest_row: i8,
+
+    /// The kick used by the last successful action, if it was a rotation
+    last_kick: Option<usize>,
+    last_lock: Option<LockResult>,
+    events: Vec<GameEvent>,
+
+    game_over: bool,
+    goal_reached: bool,
+
+    /// Ticks played, not counting any after the game ended
+    ticks: u32,
+    /// Pieces locked
+    pieces: u32,
 }
 
 impl Tetris
 {
-    pub fn new(width: u32, height: u32) -> Tetris
+    pub fn new(mut rules: Rules, seed: u64) -> Tetris
     {
-        Tetris
+        if rules.preview_count > MAX_PREVIEW
         {
-            width: width,
-            height: height,
+            rules.preview_count = MAX_PREVIEW;
+        }
 
-            key_held: None,
+        let mut randomizer = rules.randomizer.build(seed);
+        let queue = (0..rules.preview_count).map(|_| randomizer.next()).collect();
 
-            board: CellMatrix::new(10, 22),
+        let scoring = Scoring::new(rules.scoring);
+        let gravity = scoring.gravity();
+
+        let mut tetris = Tetris
+        {
+            inputs: InputFrame::empty(),
+            shift_direction: 0,
+            das_frames: 0,
+            arr_frames: 0,
+
+            rotation_system: rules.rotation.build(),
 
-            old_gravity: 0.0,
-            gravity: 1.0/60.0,
-            gravity_frame: 0,
+            seed: seed,
+            randomizer: randomizer,
+            queue: queue,
 
-            background: None,
-            tetrominos: vec![],
+            held: None,
+            hold_used: false,
+
+            rules: rules,
+
+            board: CellMatrix::new(10, 22),
             current_tetromino: None,
-        }
+
+            scoring: scoring,
+
+            soft_drop: false,
+            gravity: gravity,
+            gravity_acc: 0.0,
+
+            lock_frame: 0,
+            lock_resets: 0,
+            lowest_row: 0,
+
+            last_kick: None,
+            last_lock: None,
+            events: vec![],
+
+            game_over: false,
+            goal_reached: false,
+
+            ticks: 0,
+            pieces: 0,
+        };
+
+        tetris.new_piece();
+        tetris
     }
 
-    pub fn start(&mut self, display: &glium::backend::glutin_backend::GlutinFacade)
+    /// Throws away the current game and starts a new one with the same rules.
+    /// Nothing carries over, so the new game replays from `Tetris::new`.
+    pub fn restart(&mut self, seed: u64)
     {
-        self.new_piece(display);
-        self.setup_background(display);
+        *self = Tetris::new(self.rules.clone(), seed);
     }
 
-    pub fn update(&mut self) -> GameState
+    /// Advances the game by one tick with the given buttons held
+    pub fn step(&mut self, inputs: InputFrame) -> GameState
     {
-        self.gravity_frame += 1;
+        let previous = mem::replace(&mut self.inputs, inputs);
+        let pressed = inputs.pressed_since(previous);
+        let released = previous.pressed_since(inputs);
+
+        if released.is_held(Button::Left)
+        {
+            self.release_shift(-1);
+        }
+
+        if released.is_held(Button::Right)
+        {
+            self.release_shift(1);
+        }
+
+        if pressed.is_held(Button::Left)
+        {
+            self.press_shift(-1);
+        }
+
+        if pressed.is_held(Button::Right)
+        {
+            self.press_shift(1);
+        }
 
-        if self.gravity_frame > (1.0/self.gravity) as u32
+        self.soft_drop = inputs.is_held(Button::SoftDrop);
+
+        if pressed.is_held(Button::Hold)
         {
-            self.gravity();
-            self.gravity_frame = 0;
+            self.hold();
         }
 
-        GameState::Play
+        if pressed.is_held(Button::RotateRight)
+        {
+            self.rotate(true);
+        }
+
+        if pressed.is_held(Button::RotateLeft)
+        {
+            self.rotate(false);
+        }
+
+        if pressed.is_held(Button::Rotate180)
+        {
+            self.rotate_180();
+        }
+
+        if pressed.is_held(Button::HardDrop)
+        {
+            self.hard_drop();
+        }
+
+        self.update()
     }
 
-    pub fn handle_input(&mut self, event: Event) -> GameState
+    fn update(&mut self) -> GameState
     {
-        match event
+        if self.game_over
         {
-            Event::KeyboardInput(state, _, keycode) =>
-                self.handle_keyboard(state, keycode),
+            return GameState::GameOver;
+        }
+
+        self.ticks += 1;
+        self.update_shift();
+
+        self.gravity_acc += if self.soft_drop
+        {
+            (self.gravity * self.rules.soft_drop_factor).min(MAX_GRAVITY)
+        }
+        else
+        {
+            self.gravity
+        };
+
+        while self.gravity_acc >= 1.0
+        {
+            self.gravity_acc -= 1.0;
+
+            if !self.fall()
+            {
+                self.gravity_acc = 0.0;
 
-            _ => return GameState::Play
+                if self.rules.lock_delay == 0
+                {
+                    self.lock_piece();
+                }
+
+                break;
+            }
         }
+
+        self.update_lock_delay();
+
+        if self.rules.mode == GameMode::Ultra && self.ticks >= ULTRA_TICKS && !self.game_over
+        {
+            self.reach_goal();
+        }
+
+        self.state()
+    }
+
+    pub fn state(&self) -> GameState
+    {
+        if self.game_over { GameState::GameOver } else { GameState::Play }
     }
 
-    fn handle_keyboard(&mut self, state: ElementState, keycode: Option<VirtualKeyCode>)
-        -> GameState
+    /// Moves the piece for a held direction. The first frame moves once, then
+    /// nothing happens until DAS has charged, after which the piece moves
+    /// every ARR frames. The charge is kept across pieces.
+    fn update_shift(&mut self)
     {
+        let dx = self.shift_direction;
 
-        let key = match keycode
+        if dx == 0
         {
-            Some(x) => x,
-            None => return GameState::Play
-        };
+            return;
+        }
 
-        match (key, state)
+        if self.das_frames < self.rules.das
         {
-            (VirtualKeyCode::Left, ElementState::Pressed) =>
-                self.handle_key(key, |tetris| { tetris.move_piece(Vector2::new(-1, 0)) } ),
+            if self.das_frames == 0
+            {
+                self.shift(dx);
+            }
 
-            (VirtualKeyCode::Right, ElementState::Pressed) =>
-                self.handle_key(key, |tetris| { tetris.move_piece(Vector2::new(1, 0)) } ),
+            self.das_frames += 1;
+            return;
+        }
 
-            (VirtualKeyCode::Up, ElementState::Pressed) | (VirtualKeyCode::Z, ElementState::Pressed)  =>
-                self.handle_key(key, |tetris| { tetris.rotate_right() }),
-                
-            (VirtualKeyCode::X, ElementState::Pressed) =>
-                self.handle_key(key, |tetris| { tetris.rotate_left() }),
+        if self.rules.arr == 0
+        {
+            while self.shift(dx) {}
+        }
+        else
+        {
+            self.arr_frames += 1;
 
-            (VirtualKeyCode::Down, ElementState::Pressed) =>
+            if self.arr_frames >= self.rules.arr
             {
-                self.handle_key(key, |tetris| 
-                { 
-                    tetris.old_gravity = tetris.gravity; 
-                    tetris.gravity += 0.5; 
-                });
-            },
+                self.arr_frames = 0;
+                self.shift(dx);
+            }
+        }
+    }
+
+    /// Starts shifting in a direction. The most recently pressed direction
+    /// wins when both are held.
+    fn press_shift(&mut self, direction: i8)
+    {
+        self.shift_direction = direction;
+        self.das_frames = 0;
+        self.arr_frames = 0;
+    }
 
-            (VirtualKeyCode::Down, ElementState::Released) =>
-                self.gravity = self.old_gravity,
+    /// Stops shifting in a direction, falling back to the other one if it is
+    /// still held
+    fn release_shift(&mut self, direction: i8)
+    {
+        if self.shift_direction != direction
+        {
+            return;
+        }
+
+        let other = if direction < 0 { Button::Right } else { Button::Left };
+        let other_held = self.inputs.is_held(other);
 
-            (_, ElementState::Released) =>
+        self.shift_direction = if other_held { -direction } else { 0 };
+        self.das_frames = 0;
+        self.arr_frames = 0;
+    }
+
+    /// Counts down the lock delay while the piece rests on the stack
+    fn update_lock_delay(&mut self)
+    {
+        if self.rules.lock_delay == 0
+        {
+            return;
+        }
+
+        let grounded = match self.current_tetromino
+        {
+            Some(ref x) => x.drop_distance(&self.board) == 0,
+            None => return
+        };
+
+        if !grounded
+        {
+            return;
+        }
+
+        self.lock_frame += 1;
+
+        // Extended placement locks straight away once the resets run out
+        let out_of_resets = self.rules.lock_reset == LockReset::Extended &&
+            self.lock_resets >= MAX_LOCK_RESETS;
+
+        if self.lock_frame >= self.rules.lock_delay || out_of_resets
+        {
+            self.lock_piece();
+        }
+    }
+
+    /// Called after the player successfully moves or rotates the piece
+    fn reset_lock_delay(&mut self)
+    {
+        match self.rules.lock_reset
+        {
+            LockReset::Extended =>
             {
-                match self.key_held
+                // Only moves made while the delay is running use up a reset
+                if self.lock_frame > 0 && self.lock_resets < MAX_LOCK_RESETS
                 {
-                    Some(x) =>
-                    {
-                        if x == key
-                        {
-                            self.key_held = None;
-                        }
-                    },
-
-                    None => ()
+                    self.lock_frame = 0;
+                    self.lock_resets += 1;
                 }
             },
 
-            _ => ()
+            LockReset::Infinite => self.lock_frame = 0,
+
+            LockReset::Step => ()
+        }
+    }
+
+
+    fn move_piece(&mut self, direction: Vector2<i8>) -> bool
+    {
+        {
+            let piece = match self.current_tetromino
+            {
+                Some(ref mut x) => x,
+                None => return false
+            };
+
+            let next_pos = piece.cell_position + direction;
+
+            if piece.collides(&self.board, next_pos)
+            {
+                return false;
+            }
+
+            piece.set_position(next_pos);
         }
 
-        GameState::Play
+        self.last_kick = None;
+        true
     }
 
-    fn handle_key<F>(&mut self, key: VirtualKeyCode, mut action: F)
-        where F: FnMut(&mut Tetris)
+    /// Moves the piece sideways on behalf of the player
+    fn shift(&mut self, dx: i8) -> bool
     {
-        if self.key_held.is_none() || self.key_held.unwrap() != key
+        let moved = self.move_piece(Vector2::new(dx, 0));
+
+        if moved
+        {
+            self.reset_lock_delay();
+        }
+
+        moved
+    }
+
+    fn rotate(&mut self, clockwise: bool) -> bool
+    {
+        if self.game_over
+        {
+            return false;
+        }
+
+        let kick = match self.current_tetromino
         {
-            self.key_held = Some(key);
-            action(self);
+            Some(ref mut x) => x,
+            None => return false
+        }.rotate(&*self.rotation_system, &self.board, clockwise);
+
+        if kick.is_none()
+        {
+            return false;
         }
+
+        self.last_kick = kick;
+        self.reset_lock_delay();
+
+        true
     }
-    
-    pub fn draw_sprites(&mut self, target: &mut glium::Frame, program: &glium::Program,
-        sprite_manager: &SpriteManager, projection: &Matrix4<f32>)
+
+    /// Turns the piece twice clockwise, kicking as needed on each turn. The
+    /// piece stays put unless both turns succeed.
+    fn rotate_180(&mut self) -> bool
     {
-        let ref bg = match self.background
+        if self.game_over
+        {
+            return false;
+        }
+
+        let mut piece = match self.current_tetromino
         {
-            Some(ref x) => x,
-            None => panic!("No background sprite found!")
+            Some(ref x) => x.clone(),
+            None => return false
         };
-        
-        bg.draw(target, program, sprite_manager, projection);
-        
-        for tetromino in self.tetrominos.iter()
+
+        let first = piece.rotate(&*self.rotation_system, &self.board, true);
+        let second = piece.rotate(&*self.rotation_system, &self.board, true);
+
+        match (first, second)
         {
-            for sprite in tetromino.sprites.iter()
+            (Some(_), Some(kick)) =>
             {
-                sprite.draw(target, program, sprite_manager, projection);
-            }
+                self.current_tetromino = Some(piece);
+                self.last_kick = Some(kick);
+                self.reset_lock_delay();
+
+                true
+            },
+
+            _ => false
         }
-        
-        let ref ct = match self.current_tetromino
+    }
+
+    /// Drops the current piece straight to the bottom and locks it
+    fn hard_drop(&mut self)
+    {
+        if self.game_over
         {
-            Some(ref x) => x,
+            return;
+        }
+
+        let distance = match self.current_tetromino
+        {
+            Some(ref x) => x.drop_distance(&self.board),
             None => return
         };
-        
-        for sprite in ct.sprites.iter()
+
+        // Dropping zero rows doesn't count as a move, so a T-spin survives
+        if distance > 0
         {
-            sprite.draw(target, program, sprite_manager, projection)
+            self.move_piece(Vector2::new(0, distance as i8));
         }
+
+        self.scoring.hard_drop(distance as u32);
+        self.lock_piece();
+    }
+
+    pub fn rules(&self) -> &Rules
+    {
+        &self.rules
+    }
+
+    pub fn seed(&self) -> u64
+    {
+        self.seed
+    }
+
+    /// Ticks played so far, see `TICKS_PER_SECOND`
+    pub fn ticks(&self) -> u32
+    {
+        self.ticks
+    }
+
+    /// Pieces locked so far
+    pub fn pieces(&self) -> u32
+    {
+        self.pieces
+    }
+
+    /// The upcoming pieces, next piece first
+    pub fn preview(&self) -> &[Shape]
+    {
+        &self.queue
+    }
+
+    pub fn is_game_over(&self) -> bool
+    {
+        self.game_over
+    }
+
+    /// Whether the game ended by reaching the goal of its mode rather than
+    /// topping out
+    pub fn is_goal_reached(&self) -> bool
+    {
+        self.goal_reached
+    }
+
+    pub fn board(&self) -> &CellMatrix
+    {
+        &self.board
+    }
+
+    /// The piece being controlled, if any
+    pub fn current(&self) -> Option<&Tetromino>
+    {
+        self.current_tetromino.as_ref()
     }
 
-    fn move_piece(&mut self, direction: Vector2<i8>)
+    /// Where the current piece would land if hard dropped
+    pub fn ghost_position(&self) -> Option<Vector2<i8>>
     {
-        let piece = match self.current_tetromino
+        self.current_tetromino.as_ref().map(|x|
+            x.cell_position + Vector2::new(0, x.drop_distance(&self.board) as i8))
+    }
+
+    pub fn rotation_system(&self) -> &dyn RotationSystem
+    {
+        &*self.rotation_system
+    }
+
+    pub fn held(&self) -> Option<Shape>
+    {
+        self.held
+    }
+
+    /// Score, lines and level so far
+    pub fn score(&self) -> &Score
+    {
+        self.scoring.score()
+    }
+
+    /// What the most recently locked piece was worth
+    pub fn last_lock(&self) -> Option<&LockResult>
+    {
+        self.last_lock.as_ref()
+    }
+
+    /// Takes the events that happened since the last call
+    pub fn drain_events(&mut self) -> Vec<GameEvent>
+    {
+        mem::replace(&mut self.events, vec![])
+    }
+
+    fn push_event(&mut self, event: GameEvent)
+    {
+        if self.events.len() >= MAX_EVENTS
         {
-            Some(ref mut x) => x,
+            self.events.remove(0);
+        }
+
+        self.events.push(event);
+    }
+
+    /// Swaps the current piece with the held one, or with the next piece if
+    /// nothing is held yet. Only allowed once until the next piece locks.
+    fn hold(&mut self)
+    {
+        if self.game_over || !self.rules.hold_enabled || self.hold_used
+        {
+            return;
+        }
+
+        let shape = match self.current_tetromino.take()
+        {
+            Some(x) => x.shape,
             None => return
         };
 
-        let next_pos = piece.cell_position + direction;
+        match self.held.take()
+        {
+            Some(held) => self.spawn(held),
+            None => self.new_piece()
+        }
 
-        if !piece.collides(&self.board, next_pos)
+        self.held = Some(shape);
+        self.hold_used = true;
+    }
+
+    /// Moves the piece down a row. Returns false if it is resting on the
+    /// stack.
+    fn fall(&mut self) -> bool
+    {
+        if !self.move_piece(Vector2::new(0, 1))
         {
-            piece.set_position(next_pos);
+            return false;
+        }
+
+        if self.soft_drop
+        {
+            self.scoring.soft_drop(1);
+        }
+
+        let row = match self.current_tetromino
+        {
+            Some(ref x) => x.cell_position.y,
+            None => return false
+        };
+
+        if row > self.lowest_row
+        {
+            self.lowest_row = row;
+            self.lock_frame = 0;
+            self.lock_resets = 0;
         }
+
+        true
     }
 
-    fn rotate_right(&mut self)
+    /// Locks the current piece where it is, clears lines and spawns the next
+    /// piece
+    fn lock_piece(&mut self)
     {
-        match self.current_tetromino
+        let (tspin, top_out) = match self.current_tetromino
         {
-            Some(ref mut x) => x,
+            Some(ref x) => (tspin::detect(x, &self.board, &*self.rotation_system, self.last_kick),
+                self.lock_out(x)),
             None => return
-        }.rotate_right();
+        };
+
+        self.place_piece();
+
+        let lines = self.clear_lines();
+        let perfect_clear = lines > 0 && self.board.is_empty();
+
+        let result = self.scoring.locked(lines, tspin, perfect_clear);
+        self.gravity = self.scoring.gravity();
+
+        if tspin != TSpin::None
+        {
+            self.push_event(GameEvent::TSpin { kind: tspin, lines: lines });
+        }
+
+        self.push_event(GameEvent::Locked(result));
+        self.last_lock = Some(result);
+
+        let sprint_done = self.rules.mode == GameMode::Sprint
+            && self.scoring.score().lines >= SPRINT_LINES;
+
+        match top_out
+        {
+            Some(x) => self.top_out(x),
+            None if sprint_done => self.reach_goal(),
+            None => self.new_piece()
+        }
     }
-    
-    fn rotate_left(&mut self)
+
+    /// Checks whether locking `piece` where it is ends the game
+    fn lock_out(&self, piece: &Tetromino) -> Option<TopOut>
     {
-        match self.current_tetromino
+        let cells = piece.cells();
+
+        if cells.iter().all(|x| x.y < HIDDEN_ROWS)
         {
-            Some(ref mut x) => x,
+            Some(TopOut::LockOut)
+        }
+        else if self.rules.partial_lock_out && cells.iter().any(|x| x.y < HIDDEN_ROWS)
+        {
+            Some(TopOut::PartialLockOut)
+        }
+        else
+        {
+            None
+        }
+    }
+
+    fn top_out(&mut self, reason: TopOut)
+    {
+        self.game_over = true;
+
+        self.push_event(GameEvent::GameOver(reason));
+    }
+
+    fn reach_goal(&mut self)
+    {
+        self.game_over = true;
+        self.goal_reached = true;
+
+        self.push_event(GameEvent::GoalReached);
+    }
+
+    /// Writes the current piece into the board
+    fn place_piece(&mut self)
+    {
+        let piece = match self.current_tetromino.take()
+        {
+            Some(x) => x,
             None => return
-        }.rotate_left();
+        };
+
+        self.board.place(&piece.matrix, piece.cell_position);
+        self.hold_used = false;
+        self.pieces += 1;
     }
 
-    fn gravity(&mut self)
+    /// Removes full rows from the board. Returns the number of rows cleared.
+    fn clear_lines(&mut self) -> u32
     {
-        let velocity = Vector2::new(0, self.gravity.ceil() as i8);
+        self.board.clear_full_rows().len() as u32
+    }
+
+    /// Takes the next shape off the queue and tops the queue back up
+    fn next_shape(&mut self) -> Shape
+    {
+        self.queue.push(self.randomizer.next());
+        self.queue.remove(0)
+    }
+
+    fn new_piece(&mut self)
+    {
+        let shape = self.next_shape();
+
+        self.spawn(shape);
+    }
+
+    /// Puts a piece of the given shape at the top of the board
+    fn spawn(&mut self, shape: Shape)
+    {
+        let ct = Tetromino::new(shape, self.rotation_system.spawn_position(shape),
+            self.rotation_system.spawn_orientation(shape));
+
+        let blocked = ct.collides(&self.board, ct.cell_position);
 
+        self.lowest_row = ct.cell_position.y;
+        self.lock_frame = 0;
+        self.lock_resets = 0;
+        self.last_kick = None;
+
+        self.current_tetromino = Some(ct);
+
+        if blocked
         {
-            let piece = match self.current_tetromino
+            self.top_out(TopOut::BlockOut);
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+    use cellmatrix::Cell;
+    use events::GameEvent;
+    use input::{Button, InputFrame};
+    use rules::{GameMode, LockReset, Rules, MAX_LOCK_RESETS, ULTRA_TICKS};
+    use tetromino::Shape;
+    use super::{GameState, Tetris};
+
+    /// Taps hard drop: one frame held, one frame released
+    fn hard_drop(tetris: &mut Tetris) -> GameState
+    {
+        tetris.step(InputFrame::empty().with(Button::HardDrop));
+        tetris.step(InputFrame::empty())
+    }
+
+    /// Starts a game with the first piece resting on the floor
+    fn grounded(rules: Rules) -> Tetris
+    {
+        let mut tetris = Tetris::new(rules, 1);
+
+        while tetris.fall() {}
+
+        tetris
+    }
+
+    /// The column of the current piece
+    fn column(tetris: &Tetris) -> i8
+    {
+        tetris.current().unwrap().cell_position.x
+    }
+
+    /// Taps left on even calls and right on odd ones, so the piece wiggles in
+    /// place without reaching a wall
+    fn wiggle(tetris: &mut Tetris, i: u32)
+    {
+        let button = if i % 2 == 0 { Button::Left } else { Button::Right };
+
+        tetris.step(InputFrame::empty().with(button));
+        tetris.step(InputFrame::empty());
+    }
+
+    #[test]
+    fn hard_drop_locks_and_spawns_next()
+    {
+        let mut tetris = Tetris::new(Rules::guideline(), 1);
+        let next = tetris.preview()[0];
+
+        assert_eq!(hard_drop(&mut tetris), GameState::Play);
+
+        assert!(!tetris.board().is_empty());
+        assert_eq!(tetris.current().map(|x| x.shape), Some(next));
+    }
+
+    #[test]
+    fn stacking_in_the_middle_tops_out()
+    {
+        let mut tetris = Tetris::new(Rules::guideline(), 1);
+
+        for _ in 0..100
+        {
+            if hard_drop(&mut tetris) == GameState::GameOver
             {
-                Some(ref x) => x,
-                None => return
-            };
+                break;
+            }
+        }
 
-            let next_pos = piece.cell_position + velocity;
+        assert!(tetris.is_game_over());
+        assert!(tetris.drain_events().iter().any(|x|
+            match *x
+            {
+                GameEvent::GameOver(_) => true,
+                _ => false
+            }));
 
-            if piece.collides(&self.board, next_pos)
+        tetris.restart(2);
+
+        assert!(!tetris.is_game_over());
+        assert!(tetris.board().is_empty());
+        assert_eq!(tetris.seed(), 2);
+    }
+
+    #[test]
+    fn holding_hard_drop_drops_once()
+    {
+        let mut tetris = Tetris::new(Rules::guideline(), 1);
+        let frame = InputFrame::empty().with(Button::HardDrop);
+
+        tetris.step(frame);
+        let after_drop = tetris.board().clone();
+
+        for _ in 0..10
+        {
+            tetris.step(frame);
+        }
+
+        assert_eq!(tetris.board(), &after_drop);
+    }
+
+    #[test]
+    fn same_seed_and_inputs_same_game()
+    {
+        let frames: Vec<InputFrame> = (0..600u32).map(|i|
+            InputFrame::from_bits((i.wrapping_mul(2654435761) >> 24) as u8)).collect();
+
+        let mut a = Tetris::new(Rules::guideline(), 7);
+        let mut b = Tetris::new(Rules::guideline(), 7);
+
+        for frame in frames.iter()
+        {
+            assert_eq!(a.step(*frame), b.step(*frame));
+            assert_eq!(a.board(), b.board());
+        }
+
+        assert!(a.score() == b.score());
+    }
+
+    #[test]
+    fn ultra_ends_on_time()
+    {
+        let mut rules = Rules::guideline();
+        rules.mode = GameMode::Ultra;
+        // The first piece never locks, so only the clock can end the game
+        rules.lock_delay = ULTRA_TICKS * 2;
+
+        let mut tetris = Tetris::new(rules, 1);
+
+        for _ in 1..ULTRA_TICKS
+        {
+            assert_eq!(tetris.step(InputFrame::empty()), GameState::Play);
+        }
+
+        assert_eq!(tetris.step(InputFrame::empty()), GameState::GameOver);
+        assert!(tetris.is_goal_reached());
+        assert_eq!(tetris.ticks(), ULTRA_TICKS);
+        assert!(tetris.drain_events().contains(&GameEvent::GoalReached));
+    }
+
+    #[test]
+    fn extended_reset_stops_after_fifteen_moves()
+    {
+        let mut rules = Rules::guideline();
+        rules.lock_delay = 60;
+
+        let mut tetris = grounded(rules);
+        tetris.step(InputFrame::empty());
+
+        for i in 0..MAX_LOCK_RESETS - 1
+        {
+            wiggle(&mut tetris, i);
+        }
+
+        assert_eq!(tetris.pieces(), 0);
+
+        // The last reset is used up and the piece locks without waiting out
+        // the delay
+        wiggle(&mut tetris, MAX_LOCK_RESETS);
+
+        assert_eq!(tetris.pieces(), 1);
+    }
+
+    #[test]
+    fn infinite_reset_never_runs_out()
+    {
+        let mut rules = Rules::guideline();
+        rules.lock_delay = 60;
+        rules.lock_reset = LockReset::Infinite;
+
+        let mut tetris = grounded(rules);
+
+        for i in 0..MAX_LOCK_RESETS * 4
+        {
+            wiggle(&mut tetris, i);
+        }
+
+        assert_eq!(tetris.pieces(), 0);
+    }
+
+    #[test]
+    fn step_reset_ignores_moves()
+    {
+        let mut rules = Rules::guideline();
+        rules.lock_reset = LockReset::Step;
+
+        let mut tetris = grounded(rules);
+
+        // Each wiggle is two ticks
+        for i in 0..(tetris.rules().lock_delay - 1) / 2
+        {
+            wiggle(&mut tetris, i);
+        }
+
+        tetris.step(InputFrame::empty());
+        assert_eq!(tetris.pieces(), 0);
+
+        tetris.step(InputFrame::empty());
+        assert_eq!(tetris.pieces(), 1);
+    }
+
+    #[test]
+    fn step_reset_on_reaching_a_lower_row()
+    {
+        let mut rules = Rules::guideline();
+        rules.lock_reset = LockReset::Step;
+
+        let mut tetris = Tetris::new(rules, 1);
+        let lock_delay = tetris.rules().lock_delay;
+
+        // A ledge under the left column of an O piece
+        tetris.current_tetromino = None;
+        tetris.board.set_cell(4, 12, Cell::Occupied(Shape::IBlock));
+        tetris.spawn(Shape::OBlock);
+
+        while tetris.fall() {}
+
+        for _ in 0..lock_delay - 10
+        {
+            tetris.step(InputFrame::empty());
+        }
+
+        // Slide off the ledge and soft drop to the floor
+        tetris.step(InputFrame::empty().with(Button::Right));
+
+        let soft_drop = InputFrame::empty().with(Button::SoftDrop);
+
+        for _ in 0..60
+        {
+            if tetris.current().unwrap().drop_distance(tetris.board()) == 0
             {
-                return;
+                break;
             }
+
+            tetris.step(soft_drop);
         }
 
-        self.move_piece(velocity);
+        assert_eq!(tetris.current().unwrap().cell_position.y, 20);
+
+        // The delay starts over on the floor
+        for _ in 0..lock_delay - 10
+        {
+            tetris.step(InputFrame::empty());
+        }
+
+        assert_eq!(tetris.pieces(), 0);
     }
 
-    fn new_piece(&mut self, display: &glium::backend::glutin_backend::GlutinFacade)
+    #[test]
+    fn das_charges_before_repeating()
     {
-        /*if self.current_tetromino.is_some()
+        let mut tetris = Tetris::new(Rules::guideline(), 1);
+        let (das, arr) = (tetris.rules().das, tetris.rules().arr);
+        let start = column(&tetris);
+        let right = InputFrame::empty().with(Button::Right);
+
+        // One move on the press, then nothing while DAS charges
+        for _ in 0..das
         {
-            let ct = self.current_tetromino;
-            self.tetrominos.push(ct.expect("this is impossible"));
-        }*/
+            tetris.step(right);
+            assert_eq!(column(&tetris), start + 1);
+        }
 
-        let ct = Tetromino::new(display, Shape::LBlock, BOARD_POS, Vector2::new(3, 0));
-        self.current_tetromino = Some(ct);
+        for _ in 0..arr
+        {
+            tetris.step(right);
+        }
+
+        assert_eq!(column(&tetris), start + 2);
+    }
+
+    #[test]
+    fn zero_arr_shifts_to_the_wall()
+    {
+        let mut rules = Rules::guideline();
+        rules.arr = 0;
+
+        let mut tetris = Tetris::new(rules, 1);
+        let das = tetris.rules().das;
+        let start = column(&tetris);
+        let left = InputFrame::empty().with(Button::Left);
+
+        for _ in 0..das
+        {
+            tetris.step(left);
+        }
+
+        assert_eq!(column(&tetris), start - 1);
+
+        tetris.step(left);
+
+        let piece = tetris.current().unwrap();
+        assert!(piece.cells().iter().any(|x| x.x == 0));
     }
 
-    /// Sets up background image
-    fn setup_background(&mut self, display: &glium::backend::glutin_backend::GlutinFacade)
+    #[test]
+    fn last_pressed_direction_wins()
     {
-        self.background = Some(
-            Sprite::new(
-                display,
-                Textures::Background,
-                Rect::new(0.0, 0.0, self.width as f32, self.height as f32),
-                Vector2::new(0.0, 0.0)
-            ).unwrap()
-        );
+        let mut tetris = Tetris::new(Rules::guideline(), 1);
+        let start = column(&tetris);
+        let left = InputFrame::empty().with(Button::Left);
+        let both = left.with(Button::Right);
+
+        tetris.step(left);
+        assert_eq!(column(&tetris), start - 1);
+
+        tetris.step(both);
+        assert_eq!(column(&tetris), start);
+
+        // Letting go of right goes back to the still held left
+        tetris.step(left);
+        assert_eq!(column(&tetris), start - 1);
+
+        // Letting go of the direction that lost changes nothing
+        tetris.step(both);
+        assert_eq!(column(&tetris), start);
+
+        tetris.step(InputFrame::empty().with(Button::Right));
+        assert_eq!(column(&tetris), start);
     }
 }
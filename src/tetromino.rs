@@ -1,13 +1,10 @@
 use cgmath::Vector2;
 
-use glium;
-
 use cellmatrix::{Cell, CellMatrix};
-use rect::Rect;
-use sprite::Sprite;
-use spritemanager::Textures;
+use rotation::{Orientation, RotationSystem};
 
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Shape
 {
     IBlock,
@@ -19,58 +16,91 @@ pub enum Shape
     LBlock,
 }
 
+pub const SHAPES: [Shape; 7] = [
+    Shape::IBlock,
+    Shape::OBlock,
+    Shape::TBlock,
+    Shape::SBlock,
+    Shape::ZBlock,
+    Shape::JBlock,
+    Shape::LBlock,
+];
+
+impl Shape
+{
+    /// The tint used for every block of this shape
+    pub fn color(&self) -> [f32; 4]
+    {
+        match *self
+        {
+            Shape::IBlock => [0.0, 0.8, 0.9, 1.0],
+            Shape::OBlock => [0.95, 0.85, 0.0, 1.0],
+            Shape::TBlock => [0.65, 0.2, 0.85, 1.0],
+            Shape::SBlock => [0.3, 0.85, 0.2, 1.0],
+            Shape::ZBlock => [0.9, 0.2, 0.2, 1.0],
+            Shape::JBlock => [0.2, 0.35, 0.95, 1.0],
+            Shape::LBlock => [0.95, 0.55, 0.1, 1.0],
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Tetromino
 {
     pub shape: Shape,
 
-    pub board_position: Vector2<f32>,
     pub cell_position: Vector2<i8>,
+    pub orientation: Orientation,
 
     pub matrix: CellMatrix,
-    pub sprites: Vec<Sprite>,
 }
 
-impl  Tetromino
+impl Tetromino
 {
-    pub fn new(display: &glium::backend::glutin_backend::GlutinFacade, shape: Shape,
-        board_position: Vector2<f32>, cell_position: Vector2<i8>) -> Tetromino
+    pub fn new(shape: Shape, cell_position: Vector2<i8>, orientation: Orientation) -> Tetromino
     {
-        let matrix = Tetromino::build_matrix(&shape);
-        let sprites = Tetromino::build_sprites(display, &matrix);
+        let mut matrix = Tetromino::build_matrix(&shape);
 
-        let mut tetromino = Tetromino
+        for _ in 0..orientation.turns()
+        {
+            matrix.rotate_right();
+        }
+
+        Tetromino
         {
             shape: shape,
 
-            board_position: board_position,
             cell_position: cell_position,
+            orientation: orientation,
 
             matrix: matrix,
-            sprites: sprites
-        };
-
-        tetromino.update_sprites();
-
-        tetromino
+        }
     }
 
     pub fn set_position(&mut self, position: Vector2<i8>)
     {
         self.cell_position = position;
-        self.update_sprites();
     }
 
-
-    pub fn rotate_right(&mut self)
+    /// Rotates using the given rotation system, returning the index of the
+    /// kick used. Returns None if the rotation was blocked and the piece was
+    /// left untouched.
+    pub fn rotate(&mut self, system: &dyn RotationSystem, board: &CellMatrix, clockwise: bool)
+        -> Option<usize>
     {
-        self.matrix.rotate_right();
-        self.update_sprites();
-    }
+        match system.rotate(self, board, clockwise)
+        {
+            Some(rotation) =>
+            {
+                self.matrix = rotation.matrix;
+                self.orientation = rotation.orientation;
+                self.cell_position = rotation.position;
 
-    pub fn rotate_left(&mut self)
-    {
-        self.matrix.rotate_left();
-        self.update_sprites();
+                Some(rotation.kick)
+            },
+
+            None => None
+        }
     }
     
     pub fn collides(&self, board: &CellMatrix, next_pos: Vector2<i8>) -> bool
@@ -78,10 +108,10 @@ impl  Tetromino
         self.matrix.collides(board, next_pos)
     }
 
-    /// Moves all of the sprites based on the matrix and position
-    fn update_sprites(&mut self)
+    /// The board positions of the piece's cells
+    pub fn cells(&self) -> Vec<Vector2<i8>>
     {
-        let mut sprite_index = 0;
+        let mut cells = vec![];
 
         for y in 0..self.matrix.height
         {
@@ -89,62 +119,120 @@ impl  Tetromino
             {
                 match self.matrix.get_cell(x, y)
                 {
-                    Cell::Occupied =>
-                    {
-                        let sprite = &mut self.sprites[sprite_index];
-                        let position = self.board_position +
-                            Vector2::new((self.cell_position.x + x as i8) as f32 * 16.0,
-                                (self.cell_position.y + y as i8) as f32 * 16.0);
-
-
-                        sprite.set_position(position);
-                        sprite_index += 1;
-                    },
-
+                    Cell::Occupied(_) => cells.push(self.cell_position + Vector2::new(x as i8, y as i8)),
                     _ => ()
                 }
             }
         }
+
+        cells
+    }
+
+    /// How many rows the piece can fall before landing
+    pub fn drop_distance(&self, board: &CellMatrix) -> u8
+    {
+        self.matrix.drop_distance(board, self.cell_position)
     }
 
+    /// Builds the bounding matrix of a shape in its spawn orientation
     fn build_matrix(shape: &Shape) -> CellMatrix
     {
-        let mut matrix = CellMatrix::new(4, 4);
+        let rows: &[&str] = match *shape
+        {
+            Shape::IBlock => &["....",
+                               "####",
+                               "....",
+                               "...."],
+
+            Shape::OBlock => &["##",
+                               "##"],
+
+            Shape::TBlock => &[".#.",
+                               "###",
+                               "..."],
+
+            Shape::SBlock => &[".##",
+                               "##.",
+                               "..."],
+
+            Shape::ZBlock => &["##.",
+                               ".##",
+                               "..."],
+
+            Shape::JBlock => &["#..",
+                               "###",
+                               "..."],
+
+            Shape::LBlock => &["..#",
+                               "###",
+                               "..."],
+        };
 
-        matrix.set_cell(0, 1, Cell::Occupied);
-        matrix.set_cell(1, 1, Cell::Occupied);
-        matrix.set_cell(2, 1, Cell::Occupied);
-        matrix.set_cell(3, 1, Cell::Occupied);
+        let mut matrix = CellMatrix::new(rows[0].len() as u8, rows.len() as u8);
+
+        for (y, row) in rows.iter().enumerate()
+        {
+            for (x, c) in row.chars().enumerate()
+            {
+                if c == '#'
+                {
+                    matrix.set_cell(x as u8, y as u8, Cell::Occupied(*shape));
+                }
+            }
+        }
 
         matrix
     }
+}
 
-    fn build_sprites(display: &glium::backend::glutin_backend::GlutinFacade, matrix: &CellMatrix)
-        -> Vec<Sprite>
-    {
-        let mut sprites = Vec::<Sprite>::new();
+#[cfg(test)]
+mod tests
+{
+    use cellmatrix::Cell;
+    use super::{Shape, Tetromino, SHAPES};
 
-        for y in 0..matrix.height
+    #[test]
+    fn every_shape_has_four_cells()
+    {
+        for shape in SHAPES.iter()
         {
-            for x in 0..matrix.width
+            let matrix = Tetromino::build_matrix(shape);
+            let mut count = 0;
+
+            for y in 0..matrix.height
             {
-                match matrix.get_cell(x, y)
+                for x in 0..matrix.width
                 {
-                    Cell::Occupied =>
-                        sprites.push(
-                            Sprite::new_tinted(
-                                display,
-                                Textures::SpriteSheet,
-                                Rect::new(-8.0, -8.0, 16.0, 16.0),
-                                Vector2::new(0.0, 0.0),
-                                [0.0, 1.0, 0.0, 1.0]
-                            ).unwrap()
-                        ),
-                    _ => ()
+                    match matrix.get_cell(x, y)
+                    {
+                        Cell::Occupied(x) =>
+                        {
+                            assert_eq!(x, *shape);
+                            count += 1;
+                        },
+                        Cell::Unoccupied => ()
+                    }
                 }
             }
+
+            assert_eq!(count, 4);
         }
+    }
 
-        sprites
+    #[test]
+    fn bounding_matrix_sizes()
+    {
+        for shape in SHAPES.iter()
+        {
+            let matrix = Tetromino::build_matrix(shape);
+            let size = match *shape
+            {
+                Shape::IBlock => 4,
+                Shape::OBlock => 2,
+                _ => 3
+            };
+
+            assert_eq!((matrix.width, matrix.height), (size, size));
+        }
     }
 }
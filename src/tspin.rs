@@ -0,0 +1,155 @@
+use cgmath::Vector2;
+
+use cellmatrix::CellMatrix;
+use rotation::{Orientation, RotationSystem};
+use tetromino::{Shape, Tetromino};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TSpin
+{
+    None,
+    Mini,
+    Full,
+}
+
+/// The SRS kick that always counts as a full T-spin, even when the front
+/// corners rule would make it a mini
+const FULL_TSPIN_KICK: usize = 4;
+
+/// Checks a piece that is about to lock for a T-spin.
+///
+/// Uses the 3-corner rule: three of the four cells diagonal to the T's
+/// center must be blocked, walls and floor included. It's a full T-spin if
+/// both corners the T points at are blocked, otherwise a mini. `last_kick` is
+/// the kick used by the last action if that action was a rotation.
+pub fn detect(piece: &Tetromino, board: &CellMatrix, system: &dyn RotationSystem,
+    last_kick: Option<usize>) -> TSpin
+{
+    let kick = match last_kick
+    {
+        Some(x) => x,
+        None => return TSpin::None
+    };
+
+    if piece.shape != Shape::TBlock
+    {
+        return TSpin::None;
+    }
+
+    let center = piece.cell_position - system.offset(piece.shape, piece.orientation)
+        + Vector2::new(1, 1);
+
+    let blocked = |x: i8, y: i8| board.is_blocked(center + Vector2::new(x, y));
+
+    let top_left = blocked(-1, -1);
+    let top_right = blocked(1, -1);
+    let bottom_left = blocked(-1, 1);
+    let bottom_right = blocked(1, 1);
+
+    let corners = [top_left, top_right, bottom_left, bottom_right].iter()
+        .filter(|&&x| x)
+        .count();
+
+    if corners < 3
+    {
+        return TSpin::None;
+    }
+
+    let (front_a, front_b) = match piece.orientation
+    {
+        Orientation::Spawn => (top_left, top_right),
+        Orientation::Right => (top_right, bottom_right),
+        Orientation::Two => (bottom_left, bottom_right),
+        Orientation::Left => (top_left, bottom_left),
+    };
+
+    if (front_a && front_b) || kick == FULL_TSPIN_KICK
+    {
+        TSpin::Full
+    }
+    else
+    {
+        TSpin::Mini
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use cgmath::Vector2;
+
+    use cellmatrix::{Cell, CellMatrix};
+    use rotation::{Orientation, Srs};
+    use tetromino::{Shape, Tetromino};
+    use super::{detect, TSpin, FULL_TSPIN_KICK};
+
+    /// An empty board with the given cells filled
+    fn board(cells: &[(u8, u8)]) -> CellMatrix
+    {
+        let mut board = CellMatrix::new(10, 22);
+
+        for &(x, y) in cells
+        {
+            board.set_cell(x, y, Cell::Occupied(Shape::IBlock));
+        }
+
+        board
+    }
+
+    /// A T with its center at (5, 20)
+    fn t_piece(orientation: Orientation) -> Tetromino
+    {
+        Tetromino::new(Shape::TBlock, Vector2::new(4, 19), orientation)
+    }
+
+    #[test]
+    fn needs_three_corners()
+    {
+        let piece = t_piece(Orientation::Two);
+
+        assert_eq!(detect(&piece, &board(&[(4, 21), (6, 21)]), &Srs, Some(0)), TSpin::None);
+        assert_eq!(detect(&piece, &board(&[(4, 19), (4, 21), (6, 21)]), &Srs, Some(0)),
+            TSpin::Full);
+    }
+
+    #[test]
+    fn front_corners_decide_mini_or_full()
+    {
+        // Pointing up with only one of the corners above it blocked
+        let board = board(&[(4, 19), (4, 21), (6, 21)]);
+        let piece = t_piece(Orientation::Spawn);
+
+        assert_eq!(detect(&piece, &board, &Srs, Some(0)), TSpin::Mini);
+    }
+
+    #[test]
+    fn walls_count_as_corners()
+    {
+        // Pointing right with its back to the left wall
+        let piece = Tetromino::new(Shape::TBlock, Vector2::new(-1, 19), Orientation::Right);
+
+        assert_eq!(detect(&piece, &board(&[]), &Srs, Some(0)), TSpin::None);
+        assert_eq!(detect(&piece, &board(&[(1, 21)]), &Srs, Some(0)), TSpin::Mini);
+    }
+
+    #[test]
+    fn last_srs_kick_is_always_full()
+    {
+        let board = board(&[(4, 19), (4, 21), (6, 21)]);
+        let piece = t_piece(Orientation::Spawn);
+
+        assert_eq!(detect(&piece, &board, &Srs, Some(FULL_TSPIN_KICK)), TSpin::Full);
+    }
+
+    #[test]
+    fn only_rotated_t_pieces_count()
+    {
+        let board = board(&[(4, 19), (4, 21), (6, 21)]);
+
+        // The last action was a move or a drop
+        assert_eq!(detect(&t_piece(Orientation::Two), &board, &Srs, None), TSpin::None);
+
+        let piece = Tetromino::new(Shape::JBlock, Vector2::new(4, 19), Orientation::Two);
+        assert_eq!(detect(&piece, &board, &Srs, Some(0)), TSpin::None);
+    }
+}